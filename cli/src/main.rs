@@ -1,9 +1,21 @@
+use std::{
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+    process::ExitCode,
+    thread,
+    time::Duration,
+};
+
 use anyhow::{anyhow, Result};
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use dotenvy::dotenv;
 use lib::{
-    util::{get_project_uuid, ProjectInfo},
-    Exporter, Source,
+    cache::{self, ProjectCache},
+    cover::{DocumentMetadata, Project, ProjectPage},
+    util::{find_projects, list_projects, ProjectFilter, ProjectInfo},
+    watch::Watcher,
+    ApiError, BomFormat, Config, CoverClient, ExportOptions, Exporter, Machine, MeshFormat, Source,
+    Split,
 };
 
 #[derive(Parser)]
@@ -12,10 +24,44 @@ Transpiles Cover Datas to machine files.
 By default, the CLI uses API data but file/folder can be used with the `transpile` subcommand.
 "#)]
 struct Cli {
-    /// Path to output file
+    /// Configuration file, read instead of `machining-transpiler.toml`
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// URL of the Cover API
+    #[arg(long)]
+    api_url: Option<String>,
+
+    /// Template folder, searched before those of the configuration. Can be repeated
+    #[arg(long)]
+    template_path: Vec<PathBuf>,
+
+    /// Path to output file or directory
     #[arg(short, long)]
     output: Option<String>,
 
+    /// Write one combined job, or split it by structure view, profile or bar [default: none]
+    #[arg(short, long, value_enum)]
+    split: Option<SplitMode>,
+
+    /// Name of the output files, without extension.
+    /// Placeholders : {project}, {view}, {profile}, {bar}
+    #[arg(long)]
+    name_pattern: Option<String>,
+
+    /// Also write the bill of materials (every article type) alongside the machine file.
+    /// Needs an output
+    #[arg(long, value_enum)]
+    bom: Option<BomMode>,
+
+    /// Elumatec machine profile, from the configuration or the `machines` template
+    #[arg(short, long)]
+    machine: Option<String>,
+
+    /// Export projects from the cache only, without contacting Cover API
+    #[arg(long)]
+    offline: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -39,9 +85,83 @@ enum Commands {
         #[arg(short = 'i', long)]
         project_id: Option<u16>,
 
+        /// Project to export when several match the name, by its number in the listing
+        #[arg(long)]
+        pick: Option<usize>,
+
         /// Name of the provider (Elumatec, ...)
         #[arg(short, long)]
         vendor: String,
+
+        /// Upload the generated files to the project documents of Cover API, with
+        /// the exporter version, template hash and time of the export
+        #[arg(long)]
+        upload: bool,
+    },
+
+    /// List the projects of Cover API
+    Projects {
+        /// Only projects whose name contains this text
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+
+        /// Only projects dated on or after this day (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only projects dated on or before this day (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<String>,
+
+        /// Only projects with this status
+        #[arg(long)]
+        status: Option<String>,
+
+        /// Page to show, starting at 1
+        #[arg(long, default_value_t = 1)]
+        page: usize,
+
+        /// Projects per page
+        #[arg(long, default_value_t = 50)]
+        per_page: usize,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+    },
+
+    /// List or prune the project data cached for `--offline`
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+
+    /// Export the projects of Cover API reaching a status to a spool folder, polling
+    /// the API. Projects are exported again when their data changes
+    WatchApi {
+        /// Name of the provider (Elumatec, ...) [default: `watch.vendor`]
+        #[arg(short, long)]
+        vendor: Option<String>,
+
+        /// Status of the projects to export [default: `watch.status`, or READY_FOR_PRODUCTION]
+        #[arg(long)]
+        status: Option<String>,
+
+        /// Seconds between two polls [default: `watch.interval`, or 60]
+        #[arg(long)]
+        interval: Option<u64>,
+
+        /// Folder the exported files are moved to [default: `watch.spool`]
+        #[arg(long)]
+        spool: Option<PathBuf>,
+
+        /// File of the projects already exported [default: `.watch-state.json` of the spool]
+        #[arg(long)]
+        state: Option<PathBuf>,
+
+        /// Poll the API once, then exit
+        #[arg(long)]
+        once: bool,
     },
 
     /// Transpile JSON file or entire folder.
@@ -56,6 +176,98 @@ enum Commands {
 
         source: String,
     },
+
+    /// Render an Elumatec document, or the jobs of a JSON file, to SVG. Works offline.
+    Preview {
+        /// Elumatec document (.ncw) or JSON file
+        source: String,
+    },
+
+    /// Export each part of an Elumatec document, or of a JSON file, as a 3D mesh.
+    Mesh {
+        /// Mesh file format
+        #[arg(short, long, value_enum, default_value_t = MeshMode::Stl)]
+        format: MeshMode,
+
+        /// Elumatec document (.ncw) or JSON file
+        source: String,
+    },
+}
+
+#[derive(Subcommand, PartialEq)]
+enum CacheAction {
+    /// List cached projects
+    List,
+
+    /// Remove cached projects, all of them by default
+    Prune {
+        /// Only projects fetched more than this many days ago
+        #[arg(long)]
+        older_than: Option<u64>,
+
+        /// Only this project
+        #[arg(short = 'u', long)]
+        project_uuid: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum SplitMode {
+    /// Single job for the whole project
+    None,
+    /// One job per structure view (frame)
+    View,
+    /// One job per profile reference
+    Profile,
+    /// One job per optimized bar
+    Bar,
+}
+
+impl From<SplitMode> for Split {
+    fn from(mode: SplitMode) -> Self {
+        match mode {
+            SplitMode::None => Split::None,
+            SplitMode::View => Split::View,
+            SplitMode::Profile => Split::Profile,
+            SplitMode::Bar => Split::Bar,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum BomMode {
+    Csv,
+    Json,
+}
+
+impl From<BomMode> for BomFormat {
+    fn from(mode: BomMode) -> Self {
+        match mode {
+            BomMode::Csv => BomFormat::Csv,
+            BomMode::Json => BomFormat::Json,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ListFormat {
+    Table,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum MeshMode {
+    Stl,
+    Obj,
+}
+
+impl From<MeshMode> for MeshFormat {
+    fn from(mode: MeshMode) -> Self {
+        match mode {
+            MeshMode::Stl => MeshFormat::Stl,
+            MeshMode::Obj => MeshFormat::Obj,
+        }
+    }
 }
 
 trait CheckVendor {
@@ -72,21 +284,191 @@ impl CheckVendor for Exporter {
     }
 }
 
-fn main() -> Result<()> {
-    dotenv().expect("Unable to load environnement variables, .env file not found");
+/// Table of `projects`, numbered from `first`.
+fn project_table(projects: &[Project], first: usize) -> String {
+    let rows = std::iter::once(["#", "id", "uuid", "name", "date", "status"].map(str::to_owned))
+        .chain(projects.iter().enumerate().map(|(index, project)| {
+            [
+                (first + index).to_string(),
+                project.id.map(|id| id.to_string()).unwrap_or_default(),
+                project.uuid.clone(),
+                project.name.clone(),
+                project.date().unwrap_or_default(),
+                project.status().unwrap_or_default(),
+            ]
+        }))
+        .collect::<Vec<_>>();
+
+    let widths = (0..6)
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_owned()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Project to export among `projects` : the only one, the one numbered
+/// `pick`, or the one chosen on the terminal.
+fn pick_project(mut projects: Vec<Project>, pick: Option<usize>) -> Result<Option<Project>> {
+    if let Some(pick) = pick {
+        if pick == 0 || pick > projects.len() {
+            return Err(anyhow!(
+                "`--pick {pick}` is out of range, {} project(s) match :\n\n{}",
+                projects.len(),
+                project_table(&projects, 1)
+            ));
+        }
+
+        return Ok(Some(projects.swap_remove(pick - 1)));
+    }
+
+    if projects.len() < 2 {
+        return Ok(projects.pop());
+    }
+
+    let table = project_table(&projects, 1);
+    if !io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "{} projects match :\n\n{table}\n\nUse `--pick <N>` to choose one, or a project id or uuid",
+            projects.len()
+        ));
+    }
+
+    eprintln!("{} projects match :\n\n{table}\n", projects.len());
+    loop {
+        eprint!(
+            "Project to export [1-{}], empty to cancel : ",
+            projects.len()
+        );
+        io::stderr().flush()?;
+
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return Err(anyhow!("No project picked"));
+        }
+
+        match answer.parse::<usize>() {
+            Ok(pick) if (1..=projects.len()).contains(&pick) => {
+                return Ok(Some(projects.swap_remove(pick - 1)))
+            }
+            _ => eprintln!("`{answer}` is not a project number"),
+        }
+    }
+}
+
+/// Time elapsed since `timestamp`, in seconds since the Unix epoch.
+fn age(timestamp: u64) -> String {
+    let seconds = cache::now().saturating_sub(timestamp);
+
+    match seconds {
+        0..3600 => format!("{} min", seconds / 60),
+        3600..86400 => format!("{} h", seconds / 3600),
+        _ => format!("{} d", seconds / 86400),
+    }
+}
+
+/// Exit code when the Cover API can not be reached or fails.
+const API_ERROR: u8 = 3;
+
+fn main() -> ExitCode {
+    let Err(err) = run() else {
+        return ExitCode::SUCCESS;
+    };
+
+    eprintln!("Error: {err:?}");
+
+    match err
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<ApiError>())
+    {
+        Some(api_error) => {
+            if api_error
+                .status
+                .is_none_or(|status| status.is_server_error())
+            {
+                eprintln!(
+                    "\nThe Cover API is unavailable : check the network connection and `api.url`, then try again."
+                );
+            }
+
+            ExitCode::from(API_ERROR)
+        }
+        None => ExitCode::FAILURE,
+    }
+}
+
+fn run() -> Result<()> {
+    // Variables of a `.env` file are part of the environment layer
+    dotenv().ok();
 
     let cli = Cli::parse();
-    let exporter = Exporter::new();
+    let mut config = Config::load(cli.config.as_deref())?;
+    config.merge(Config {
+        api: lib::config::Api {
+            url: cli.api_url,
+            ..Default::default()
+        },
+        templates: lib::config::Templates {
+            paths: cli.template_path,
+        },
+        ..Default::default()
+    });
+
+    let exporter = Exporter::new(&config)?;
+    let output = cli.output.or(config.output.directory.clone());
+    let options = ExportOptions {
+        split: cli
+            .split
+            .map(Split::from)
+            .or(config.output.split)
+            .unwrap_or_default(),
+        name_pattern: cli.name_pattern.or(config.output.name_pattern.clone()),
+        bom: cli.bom.map(BomFormat::from).or(config.output.bom),
+        machine: cli
+            .machine
+            .map(|name| Machine::load(&config, &name))
+            .transpose()?,
+    };
 
     match cli.command {
         Commands::FromApi {
             project_name,
             project_uuid,
             project_id,
+            pick,
             vendor,
+            upload,
         } => {
             exporter.check_vendor(&vendor)?;
 
+            if upload && cli.offline {
+                return Err(anyhow!(
+                    "`--upload` needs Cover API, it can not be used `--offline`"
+                ));
+            }
+            if upload && output.is_none() {
+                return Err(anyhow!(
+                    "`--upload` needs `--output` : printed programs can not be uploaded"
+                ));
+            }
+
             // Check project existence
 
             if project_name.is_none() && project_id.is_none() && project_uuid.is_none() {
@@ -95,20 +477,235 @@ fn main() -> Result<()> {
                 ));
             }
 
-            let mut project_uuid: Option<String> = None;
-            if let Some(project_name) = project_name {
-                project_uuid = get_project_uuid(ProjectInfo::Name(project_name));
+            let project_info = if let Some(project_name) = project_name {
+                ProjectInfo::Name(project_name)
             } else if let Some(project_id) = project_id {
-                project_uuid = get_project_uuid(ProjectInfo::Id(project_id));
-            } else if let Some(uuid) = project_uuid {
-                project_uuid = get_project_uuid(ProjectInfo::Uuid(uuid));
+                ProjectInfo::Id(project_id)
+            } else {
+                ProjectInfo::Uuid(project_uuid.unwrap())
+            };
+
+            let cache = ProjectCache::open(&config)?;
+            let client = if cli.offline {
+                None
+            } else {
+                Some(CoverClient::new(&config.api)?)
+            };
+
+            let (project_uuid, data) = if let Some(client) = &client {
+                let Some(project) = pick_project(find_projects(client, project_info)?, pick)?
+                else {
+                    return Err(anyhow!("Project not found"));
+                };
+
+                println!("Using project {}\n", project.uuid);
+                let data = cache.fetch(client, &project)?;
+                (project.uuid, data)
+            } else {
+                let Some(project) = pick_project(cache.find(project_info)?, pick)? else {
+                    return Err(anyhow!(
+                        "Project not found in the cache {}",
+                        cache.folder().display()
+                    ));
+                };
+
+                let entry = cache
+                    .get(&project.uuid)?
+                    .ok_or(anyhow!("Project {} is not cached", project.uuid))?;
+                println!(
+                    "Using cached project {} ({} old)\n",
+                    project.uuid,
+                    age(entry.fetched)
+                );
+                (project.uuid, entry.data)
+            };
+
+            let source = Source::Api {
+                project_uuid: project_uuid.clone(),
+                data,
+            };
+            let exported = exporter.export(source, &vendor, output, &options)?;
+
+            if let (true, Some(client)) = (upload, &client) {
+                let metadata = DocumentMetadata {
+                    project_uuid,
+                    vendor: vendor.clone(),
+                    exporter_version: env!("CARGO_PKG_VERSION").to_owned(),
+                    template: exported.template,
+                    template_hash: exported.template_hash,
+                    timestamp: cache::now(),
+                };
+                client.upload_files(&exported.files, &metadata)?;
+                for file in &exported.files {
+                    println!(
+                        "Uploaded {} to project {}",
+                        file.display(),
+                        metadata.project_uuid
+                    );
+                }
+            }
+        }
+
+        Commands::Projects {
+            name,
+            since,
+            until,
+            status,
+            page,
+            per_page,
+            format,
+        } => {
+            if page == 0 || per_page == 0 {
+                return Err(anyhow!("`--page` and `--per-page` start at 1"));
             }
 
-            if let Some(project_uuid) = project_uuid {
-                println!("Using project {project_uuid}\n");
-                exporter.export(Source::Api { project_uuid }, &vendor, cli.output)?;
+            let client = CoverClient::new(&config.api)?;
+            let filter = ProjectFilter {
+                name,
+                since,
+                until,
+                status,
+            };
+
+            let first = (page - 1) * per_page;
+            let local = |projects: Vec<Project>| {
+                let total = projects.len();
+                let shown = projects.into_iter().skip(first).take(per_page).collect();
+                (shown, total)
+            };
+
+            // Without filters, only the page shown is requested
+            let (shown, total): (Vec<Project>, usize) = if filter.is_empty() {
+                match client.project_page(page - 1, per_page)? {
+                    ProjectPage {
+                        projects,
+                        total: Some(total),
+                        ..
+                    } => (projects, total),
+
+                    // Lists the API does not page hold every project
+                    ProjectPage {
+                        projects,
+                        pages: None,
+                        ..
+                    } => local(projects),
+
+                    _ => local(client.projects()?),
+                }
             } else {
-                return Err(anyhow!("Project not found"));
+                local(list_projects(&client, &filter)?)
+            };
+            let pages = total.div_ceil(per_page).max(1);
+
+            match format {
+                ListFormat::Table => {
+                    println!("{}", project_table(&shown, first + 1));
+                    println!("\nPage {page}/{pages}, {total} project(s) in total");
+                }
+                ListFormat::Json => println!("{}", serde_json::to_string_pretty(&shown)?),
+            }
+        }
+
+        Commands::Cache { action } => {
+            let cache = ProjectCache::open(&config)?;
+
+            match action {
+                CacheAction::List => {
+                    let entries = cache.entries()?;
+                    let projects = entries
+                        .iter()
+                        .map(|entry| entry.project.clone())
+                        .collect::<Vec<_>>();
+
+                    let table = project_table(&projects, 1);
+                    for (line, entry) in table.lines().zip(
+                        std::iter::once("fetched".to_owned()).chain(
+                            entries
+                                .iter()
+                                .map(|entry| format!("{} ago", age(entry.fetched))),
+                        ),
+                    ) {
+                        println!(
+                            "{line:width$}  {entry}",
+                            width = table.lines().map(str::len).max().unwrap()
+                        );
+                    }
+                    println!(
+                        "\n{} project(s) in {}",
+                        entries.len(),
+                        cache.folder().display()
+                    );
+                }
+
+                CacheAction::Prune {
+                    older_than,
+                    project_uuid,
+                } => {
+                    let removed = match project_uuid {
+                        Some(uuid) => cache.remove(&uuid)?.into_iter().collect(),
+                        None => cache
+                            .prune(older_than.map(|days| Duration::from_secs(days * 24 * 3600)))?,
+                    };
+
+                    for entry in &removed {
+                        println!("Removed {} ({})", entry.project.uuid, entry.project.name);
+                    }
+                    println!("{} project(s) removed", removed.len());
+                }
+            }
+        }
+
+        Commands::WatchApi {
+            vendor,
+            status,
+            interval,
+            spool,
+            state,
+            once,
+        } => {
+            if cli.offline {
+                return Err(anyhow!(
+                    "`watch-api` polls Cover API, it can not run `--offline`"
+                ));
+            }
+
+            config.merge(Config {
+                watch: lib::config::Watch {
+                    vendor,
+                    status,
+                    interval,
+                    spool,
+                    state,
+                },
+                ..Default::default()
+            });
+            if let Some(vendor) = &config.watch.vendor {
+                exporter.check_vendor(vendor)?;
+            }
+
+            let client = CoverClient::new(&config.api)?;
+            let cache = ProjectCache::open(&config)?;
+            let watcher = Watcher::new(&client, &exporter, &cache, &config.watch, options)?;
+
+            println!(
+                "Watching Cover API {} for `{}` projects, exported to {}",
+                client.url(),
+                watcher.status(),
+                watcher.spool().display()
+            );
+
+            loop {
+                match watcher.poll() {
+                    Ok(_) => {}
+                    Err(err) if once => return Err(err),
+                    // The API may come back by the next poll
+                    Err(err) => eprintln!("{err:#}"),
+                }
+
+                if once {
+                    break;
+                }
+                thread::sleep(watcher.interval());
             }
         }
 
@@ -120,12 +717,20 @@ fn main() -> Result<()> {
             exporter.check_vendor(&vendor)?;
 
             if recursive {
-                exporter.transpile_folder(&source, &vendor, cli.output)?;
+                exporter.transpile_folder(&source, &vendor, output, &options)?;
             } else {
-                exporter.export(Source::File(source), &vendor, cli.output)?;
+                exporter.export(Source::File(source), &vendor, output, &options)?;
             }
         }
 
+        Commands::Preview { source } => {
+            lib::preview::preview(&source, output, &options, &config.templates)?;
+        }
+
+        Commands::Mesh { format, source } => {
+            lib::mesh::mesh(&source, output, format.into(), &options, &config.templates)?;
+        }
+
         Commands::Vendors => {
             let vendors = exporter.vendors();
             println!("Impemented providers : ");
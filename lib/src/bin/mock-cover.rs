@@ -0,0 +1,19 @@
+use anyhow::{anyhow, Result};
+use lib::mock::MockServer;
+
+/// Serves the projects of a folder as the Cover API would, for local runs of
+/// `from-api`. Needs the `mock` feature :
+/// `cargo run -p lib --features mock --bin mock-cover -- <folder>`.
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let folder = args.next().ok_or(anyhow!(
+        "Usage : mock-cover <folder> [address, 127.0.0.1:5000 by default]"
+    ))?;
+    let address = args.next().unwrap_or("127.0.0.1:5000".to_owned());
+
+    let server = MockServer::start(&folder, &address)?;
+    println!("Mock Cover API serving {folder} on {}", server.url());
+    server.wait();
+
+    Ok(())
+}
@@ -0,0 +1,248 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{cover::ProjectData, part::number, Source};
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BomFormat {
+    Csv,
+    Json,
+}
+
+impl BomFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+}
+
+/// An article of the bill of materials, quantities of identical articles being summed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BomLine {
+    pub kind: String,
+    pub reference: String,
+    pub description: String,
+    pub quantity: f64,
+    pub length: Option<f64>,
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+}
+
+impl BomLine {
+    fn from_article(kind: &str, article: &Value) -> Self {
+        let text = |keys: &[&str]| {
+            keys.iter()
+                .find_map(|key| article[key].as_str())
+                .unwrap_or_default()
+                .to_owned()
+        };
+
+        BomLine {
+            kind: kind.to_owned(),
+            reference: text(&["reference", "name"]),
+            description: text(&["description", "label"]),
+            quantity: ["quantity", "count"]
+                .iter()
+                .find_map(|key| number(&article[key]))
+                .unwrap_or(1.0),
+            length: number(&article["length"]),
+            width: number(&article["width"]),
+            height: number(&article["height"]),
+        }
+    }
+
+    fn same_article(&self, other: &BomLine) -> bool {
+        self.kind == other.kind
+            && self.reference == other.reference
+            && self.length == other.length
+            && self.width == other.width
+            && self.height == other.height
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Bom {
+    pub lines: Vec<BomLine>,
+}
+
+impl Bom {
+    pub fn from_source(source: &Source) -> Result<Self> {
+        match source {
+            Source::Api { data, .. } => Self::from_project_data(data),
+
+            Source::File(path) => {
+                let contents = fs::read_to_string(path)?;
+                Self::from_articles(&serde_json::from_str(&contents)?)
+            }
+        }
+    }
+
+    /// Reads every article of a Cover export file, whatever its type.
+    pub fn from_articles(json: &Value) -> Result<Self> {
+        let articles = json["articles"].as_array().ok_or(anyhow!(
+            "Unable to read bill of materials, `articles` is not set"
+        ))?;
+
+        let mut bom = Bom::default();
+        for article in articles {
+            let kind = article["type"].as_str().unwrap_or("unknown");
+            bom.add(BomLine::from_article(kind, article));
+        }
+
+        Ok(bom)
+    }
+
+    /// Reads every nomenclature list (profiles, glasses, hardware...) of every structure view.
+    pub fn from_project_data(data: &ProjectData) -> Result<Self> {
+        let structure_views = data.structure_views.as_ref().ok_or(anyhow!(
+            "Unable to read bill of materials, `structureViews` is null"
+        ))?;
+
+        let mut bom = Bom::default();
+        for structure_view in structure_views {
+            let Some(nomenclature) = structure_view["nomenclature"].as_object() else {
+                continue;
+            };
+
+            for (kind, articles) in nomenclature {
+                let Some(articles) = articles.as_array() else {
+                    continue;
+                };
+
+                for article in articles {
+                    bom.add(BomLine::from_article(kind, article));
+                }
+            }
+        }
+
+        Ok(bom)
+    }
+
+    fn add(&mut self, line: BomLine) {
+        match self
+            .lines
+            .iter_mut()
+            .find(|other| other.same_article(&line))
+        {
+            Some(other) => other.quantity += line.quantity,
+            None => self.lines.push(line),
+        }
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = "type,reference,description,quantity,length,width,height\n".to_owned();
+
+        for line in &self.lines {
+            let number = |value: Option<f64>| value.map(|value| value.to_string());
+            let fields = [
+                Some(line.kind.clone()),
+                Some(line.reference.clone()),
+                Some(line.description.clone()),
+                Some(line.quantity.to_string()),
+                number(line.length),
+                number(line.width),
+                number(line.height),
+            ];
+
+            let fields = fields
+                .into_iter()
+                .map(|field| csv_field(&field.unwrap_or_default()))
+                .collect::<Vec<_>>();
+
+            csv += &fields.join(",");
+            csv += "\n";
+        }
+
+        csv
+    }
+
+    pub fn to_json(&self) -> String {
+        let lines = self
+            .lines
+            .iter()
+            .map(|line| {
+                json!({
+                    "type": line.kind,
+                    "reference": line.reference,
+                    "description": line.description,
+                    "quantity": line.quantity,
+                    "length": line.length,
+                    "width": line.width,
+                    "height": line.height,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        serde_json::to_string_pretty(&lines).unwrap()
+    }
+
+    pub fn serialize(&self, format: BomFormat) -> String {
+        match format {
+            BomFormat::Csv => self.to_csv(),
+            BomFormat::Json => self.to_json(),
+        }
+    }
+
+    pub fn write(&self, format: BomFormat, path: &Path) -> Result<()> {
+        fs::write(path, self.serialize(format))?;
+        Ok(())
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sum_identical_articles() {
+        let json = json!({
+            "articles": [
+                { "type": "profile", "reference": "L100", "length": { "value": 1000 } },
+                { "type": "glass", "reference": "G4", "quantity": 2, "width": 500, "height": 800 },
+                { "type": "profile", "reference": "L100", "length": { "value": 1000 } },
+                { "type": "glass", "reference": "G4", "quantity": 1, "width": 500, "height": 800 },
+            ]
+        });
+
+        let bom = Bom::from_articles(&json).unwrap();
+        assert_eq!(bom.lines.len(), 2);
+        assert_eq!(bom.lines[0].quantity, 2.0);
+        assert_eq!(bom.lines[0].length, Some(1000.0));
+        assert_eq!(bom.lines[1].kind, "glass");
+        assert_eq!(bom.lines[1].quantity, 3.0);
+    }
+
+    #[test]
+    fn read_every_nomenclature_list() {
+        let data: ProjectData = serde_json::from_value(json!({
+            "structureViews": [
+                { "nomenclature": {
+                    "profiles": [{ "reference": "L100", "length": 1000 }],
+                    "gaskets": [{ "reference": "J1", "length": 3000, "description": "EPDM, black" }],
+                } },
+                { "nomenclature": null },
+            ]
+        }))
+        .unwrap();
+
+        let bom = Bom::from_project_data(&data).unwrap();
+        assert_eq!(bom.lines.len(), 2);
+        assert!(bom
+            .to_csv()
+            .contains("gaskets,J1,\"EPDM, black\",1,3000,,\n"));
+    }
+}
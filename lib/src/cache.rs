@@ -0,0 +1,386 @@
+use std::{
+    cmp::Reverse,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{Config, APP},
+    cover::{Project, ProjectData},
+    util::{prefer_exact, ProjectInfo},
+    CoverClient,
+};
+
+/// Project data saved on disk, one file per project uuid, so that projects
+/// already fetched can be exported without the API.
+pub struct ProjectCache {
+    folder: PathBuf,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Entry {
+    pub project: Project,
+
+    /// ETag of the data, when the API sends one.
+    pub etag: Option<String>,
+
+    /// When the data was downloaded, in seconds since the Unix epoch.
+    pub fetched: u64,
+    pub data: ProjectData,
+}
+
+impl ProjectCache {
+    pub fn new(folder: impl Into<PathBuf>) -> Self {
+        Self {
+            folder: folder.into(),
+        }
+    }
+
+    /// Cache of `cache.directory`, or of the user cache directory.
+    pub fn open(config: &Config) -> Result<Self> {
+        let folder = config
+            .cache
+            .directory
+            .clone()
+            .or_else(user_cache_dir)
+            .ok_or(anyhow!(
+                "No cache directory found : use `cache.directory` of the configuration"
+            ))?;
+
+        Ok(Self::new(folder))
+    }
+
+    pub fn folder(&self) -> &Path {
+        &self.folder
+    }
+
+    fn path(&self, uuid: &str) -> PathBuf {
+        // Uuids are used as file names, anything else is kept out of paths
+        let name = uuid
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+
+        self.folder.join(format!("{name}.json"))
+    }
+
+    pub fn get(&self, uuid: &str) -> Result<Option<Entry>> {
+        let path = self.path(uuid);
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Unable to read cache entry {}", path.display()))?;
+
+        Ok(Some(serde_json::from_str(&contents).with_context(
+            || format!("Invalid cache entry {}", path.display()),
+        )?))
+    }
+
+    pub fn put(&self, entry: &Entry) -> Result<()> {
+        fs::create_dir_all(&self.folder).with_context(|| {
+            format!("Unable to create cache directory {}", self.folder.display())
+        })?;
+
+        let path = self.path(&entry.project.uuid);
+        fs::write(&path, serde_json::to_string(entry)?)
+            .with_context(|| format!("Unable to write cache entry {}", path.display()))
+    }
+
+    /// Every readable entry, most recently fetched first. Invalid entries are
+    /// reported on stderr.
+    pub fn entries(&self) -> Result<Vec<Entry>> {
+        if !self.folder.is_dir() {
+            return Ok(vec![]);
+        }
+
+        let mut entries = vec![];
+        for file in fs::read_dir(&self.folder)? {
+            let path = file?.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+
+            let entry = fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|contents| Ok(serde_json::from_str::<Entry>(&contents)?));
+            match entry {
+                Ok(entry) => entries.push(entry),
+                Err(err) => eprintln!("Invalid cache entry {} : {err}", path.display()),
+            }
+        }
+
+        entries.sort_by_key(|entry| Reverse(entry.fetched));
+        Ok(entries)
+    }
+
+    /// Cached projects matching `project_info`, names being matched as
+    /// `util::find_projects` does.
+    pub fn find(&self, project_info: ProjectInfo) -> Result<Vec<Project>> {
+        let projects = self.entries()?.into_iter().map(|entry| entry.project);
+
+        Ok(match project_info {
+            ProjectInfo::Id(id) => projects
+                .filter(|project| project.id == Some(id.into()))
+                .collect(),
+            ProjectInfo::Uuid(uuid) => projects.filter(|project| project.uuid == uuid).collect(),
+            ProjectInfo::Name(name) => prefer_exact(
+                &name,
+                projects
+                    .filter(|project| {
+                        project
+                            .name
+                            .to_lowercase()
+                            .contains(&name.trim().to_lowercase())
+                    })
+                    .collect(),
+            ),
+        })
+    }
+
+    /// Removes the entry of `uuid`, returning it if there was one.
+    pub fn remove(&self, uuid: &str) -> Result<Option<Entry>> {
+        let entry = self.get(uuid)?;
+        if entry.is_some() {
+            let path = self.path(uuid);
+            fs::remove_file(&path)
+                .with_context(|| format!("Unable to remove cache entry {}", path.display()))?;
+        }
+
+        Ok(entry)
+    }
+
+    /// Removes the entries fetched before `older_than` ago, or all of them,
+    /// and returns them.
+    pub fn prune(&self, older_than: Option<Duration>) -> Result<Vec<Entry>> {
+        let limit = older_than.map(|age| now().saturating_sub(age.as_secs()));
+
+        let mut removed = vec![];
+        for entry in self.entries()? {
+            if limit.is_some_and(|limit| entry.fetched >= limit) {
+                continue;
+            }
+
+            let path = self.path(&entry.project.uuid);
+            fs::remove_file(&path)
+                .with_context(|| format!("Unable to remove cache entry {}", path.display()))?;
+            removed.push(entry);
+        }
+
+        Ok(removed)
+    }
+
+    /// Data of `project`, downloaded unless the cached copy is up to date : the
+    /// project has the same update date, or the API answers that the data
+    /// still matches its ETag. Downloaded data is cached.
+    pub fn fetch(&self, client: &CoverClient, project: &Project) -> Result<ProjectData> {
+        let cached = self.get(&project.uuid).unwrap_or_else(|err| {
+            eprintln!("{err:#}");
+            None
+        });
+
+        if let Some(entry) = &cached {
+            if let Some(date) = project
+                .updated()
+                .filter(|date| Some(date) == entry.project.updated().as_ref())
+            {
+                eprintln!(
+                    "Using cached data of project {} : not updated since {date}",
+                    project.uuid
+                );
+                return Ok(entry.data.clone());
+            }
+        }
+
+        let etag = cached.as_ref().and_then(|entry| entry.etag.clone());
+        let entry = match (
+            client.project_data_if_changed(&project.uuid, etag.as_deref())?,
+            cached,
+        ) {
+            (Some((data, etag)), _) => Entry {
+                project: project.clone(),
+                etag,
+                fetched: now(),
+                data,
+            },
+
+            (None, Some(entry)) => {
+                eprintln!(
+                    "Using cached data of project {} : not modified",
+                    project.uuid
+                );
+                Entry {
+                    project: project.clone(),
+                    fetched: now(),
+                    ..entry
+                }
+            }
+
+            (None, None) => {
+                return Err(anyhow!(
+                    "Cover API found data of project {} unchanged, but it is not cached",
+                    project.uuid
+                ))
+            }
+        };
+
+        if let Err(err) = self.put(&entry) {
+            eprintln!("{err:#}");
+        }
+
+        Ok(entry.data)
+    }
+}
+
+/// Seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Cache directory of the current user.
+fn user_cache_dir() -> Option<PathBuf> {
+    let folder = if cfg!(windows) {
+        PathBuf::from(std::env::var_os("LOCALAPPDATA")?)
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| Some(Path::new(&std::env::var_os("HOME")?).join(".cache")))?
+    };
+
+    Some(folder.join(APP))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Api, mock::MockServer};
+
+    fn entry(uuid: &str, name: &str, date: &str, fetched: u64) -> Entry {
+        Entry {
+            project: serde_json::from_value(serde_json::json!({
+                "id": 8,
+                "uuid": uuid,
+                "name": name,
+                "updatedAt": date,
+            }))
+            .unwrap(),
+            etag: None,
+            fetched,
+            data: serde_json::from_value(serde_json::json!({ "structureViews": [] })).unwrap(),
+        }
+    }
+
+    #[test]
+    fn reuse_and_prune_entries() {
+        let temp = tempfile::tempdir().unwrap();
+        let folder = temp.path();
+        let cache = ProjectCache::new(folder);
+
+        cache
+            .put(&entry("p1", "import", "2026-03-15", now()))
+            .unwrap();
+        cache
+            .put(&entry("p2", "Import 2", "2026-03-16", now() - 3600))
+            .unwrap();
+        assert_eq!(cache.entries().unwrap().len(), 2);
+
+        let uuids = |projects: Vec<Project>| {
+            projects
+                .into_iter()
+                .map(|project| project.uuid)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            uuids(cache.find(ProjectInfo::Name("Import".to_owned())).unwrap()),
+            ["p1"]
+        );
+        assert_eq!(
+            uuids(cache.find(ProjectInfo::Name("port".to_owned())).unwrap()),
+            ["p1", "p2"]
+        );
+        assert_eq!(uuids(cache.find(ProjectInfo::Id(8)).unwrap()).len(), 2);
+        assert_eq!(
+            uuids(cache.find(ProjectInfo::Uuid("p2".to_owned())).unwrap()),
+            ["p2"]
+        );
+
+        // Nothing listens there : up to date entries need no request
+        let client = CoverClient::new(&Api {
+            url: Some("http://127.0.0.1:9".to_owned()),
+            retries: Some(0),
+            ..Default::default()
+        })
+        .unwrap();
+        let project = cache.get("p1").unwrap().unwrap().project;
+        assert!(cache.fetch(&client, &project).is_ok());
+
+        let mut updated = project.clone();
+        updated
+            .other
+            .insert("updatedAt".to_owned(), "2026-03-17".into());
+        assert!(cache.fetch(&client, &updated).is_err());
+
+        let removed = cache.prune(Some(Duration::from_secs(60))).unwrap();
+        assert_eq!(
+            uuids(removed.into_iter().map(|entry| entry.project).collect()),
+            ["p2"]
+        );
+        assert_eq!(cache.prune(None).unwrap().len(), 1);
+        assert!(cache.entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn download_projects_without_update_date() {
+        let temp = tempfile::tempdir().unwrap();
+        let folder = temp.path();
+        let project: Project = serde_json::from_value(serde_json::json!({
+            "uuid": "p1",
+            "name": "import",
+            "createdAt": "2026-03-15",
+        }))
+        .unwrap();
+        fs::write(
+            folder.join("projects.json"),
+            serde_json::to_string(&[&project]).unwrap(),
+        )
+        .unwrap();
+        let write = |length: f64| {
+            let data = serde_json::json!({ "structureViews": [{ "length": length }] });
+            fs::write(folder.join("p1.json"), data.to_string()).unwrap();
+        };
+
+        let server = MockServer::start(folder, "127.0.0.1:0").unwrap();
+        let client = CoverClient::new(&Api {
+            url: Some(server.url()),
+            retries: Some(0),
+            ..Default::default()
+        })
+        .unwrap();
+        let cache = ProjectCache::new(folder.join("cache"));
+
+        write(1000.0);
+        cache.fetch(&client, &project).unwrap();
+
+        // The creation date stays the same when the data changes
+        write(1250.0);
+        let data = cache.fetch(&client, &project).unwrap();
+        assert_eq!(
+            data.structure_views.unwrap()[0]["length"].as_f64(),
+            Some(1250.0)
+        );
+    }
+}
@@ -0,0 +1,353 @@
+use anyhow::{anyhow, Result};
+
+use crate::{
+    document::{number, read_cuts, Bar, Cut, Work},
+    exporter::elumatec::{read_tags, tag::Tag},
+    part::Face,
+};
+
+/// Step of the search for free clamp positions, in millimeters.
+const STEP: f32 = 1.0;
+
+/// Safety distances of the `:OPTIONS` block.
+#[derive(Clone, Copy, Debug, Default)]
+struct Safety {
+    /// `OSecClampDistX/Y/Z` : between the jaws and the works.
+    clamp: (f32, f32, f32),
+    /// `OSecCollDist` : around the tools.
+    collision: f32,
+    /// `OClampMode` : the machine positions the clamps unless zero.
+    mode: i32,
+}
+
+impl Safety {
+    fn from_tags(tags: &[Tag]) -> Self {
+        let options = tags.iter().find(|tag| tag.name == "OPTIONS");
+        let value = |attr: &str| options.and_then(|tag| number(tag, attr));
+
+        Safety {
+            clamp: (
+                value("OSecClampDistX").unwrap_or_default(),
+                value("OSecClampDistY").unwrap_or_default(),
+                value("OSecClampDistZ").unwrap_or_default(),
+            ),
+            collision: value("OSecCollDist").unwrap_or_default(),
+            mode: value("OClampMode").map_or(1, |mode| mode as i32),
+        }
+    }
+}
+
+/// A problem found in the clamp zones of a cut.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Issue {
+    pub message: String,
+    /// The part cannot be clamped at all, rather than the clamps having to move.
+    pub blocking: bool,
+}
+
+impl Issue {
+    fn warning(message: String) -> Self {
+        Issue {
+            message,
+            blocking: false,
+        }
+    }
+
+    fn blocking(message: String) -> Self {
+        Issue {
+            message,
+            blocking: true,
+        }
+    }
+}
+
+/// Lowest section height across `from..to`, from the `BColBox*` envelope.
+fn height_between(bar: &Bar, from: f32, to: f32) -> f32 {
+    bar.envelope
+        .iter()
+        .filter(|(start, end, _)| *start <= to && *end >= from)
+        .map(|(_, _, height)| *height)
+        .reduce(f32::min)
+        .unwrap_or(bar.height)
+}
+
+/// Whether the jaws reach `work` on its face. Jaws grip the front and back
+/// faces from the bottom, top works are reached on lower steps of the section.
+fn under_jaws(work: &Work, bar: &Bar, height: f32, safety: &Safety) -> bool {
+    let (_, distance_y, distance_z) = safety.clamp;
+
+    match work.face {
+        Face::Bottom => true,
+        Face::Front | Face::Back => bar.height - work.y <= height + distance_z,
+        Face::Top => {
+            height_between(bar, work.y - distance_y, work.y + distance_y) <= height + distance_z
+        }
+    }
+}
+
+fn label(work: &Work) -> String {
+    if work.comment.is_empty() {
+        format!("work {}", work.number)
+    } else {
+        format!("work {} `{}`", work.number, work.comment)
+    }
+}
+
+/// Simulates a clamp at each end of `cut`, as close to the ends as the works
+/// allow. Returns the works found in the clamp zones and where to move the
+/// clamps.
+fn check_cut(cut: &Cut, safety: &Safety) -> Vec<Issue> {
+    let Some(jaws) = cut.bar.jaws else {
+        return vec![];
+    };
+    let (distance_x, _, _) = safety.clamp;
+
+    // Jaws need the full section, past the mitres
+    let mitre = |(h, v): (f32, f32)| {
+        (cut.bar.height / h.to_radians().tan()).abs() + (cut.bar.width / v.to_radians().tan()).abs()
+    };
+    let (start, end) = (mitre(cut.left), cut.length - mitre(cut.right));
+
+    let needed = jaws.length * 2.0 + distance_x;
+    if end - start < needed {
+        return vec![Issue::blocking(format!(
+            "Cut {} : part too short to be clamped, {:.1} mm of full section for {needed:.1} mm needed",
+            cut.number,
+            (end - start).max(0.0)
+        ))];
+    }
+
+    let zones = cut
+        .works
+        .iter()
+        .filter(|work| under_jaws(work, &cut.bar, jaws.height, safety))
+        .filter_map(|work| {
+            let (diameter, length) = work.shape()?;
+            let reach = (diameter + length) / 2.0 + safety.collision;
+            Some((work, work.x - reach, work.x + reach))
+        })
+        .collect::<Vec<_>>();
+
+    // Works in the zone of jaws starting at `position`
+    let blocking = |position: f32| {
+        zones
+            .iter()
+            .filter(|(_, from, to)| {
+                *from < position + jaws.length + distance_x && *to > position - distance_x
+            })
+            .map(|(work, _, _)| *work)
+            .collect::<Vec<_>>()
+    };
+    let positions = |from: f32, to: f32| {
+        let count = ((to - from).abs() / STEP).floor() as usize;
+        (0..=count).map(move |index| from + (to - from).signum() * index as f32 * STEP)
+    };
+
+    let mut issues = vec![];
+    let mut left = start;
+    for side in ["left", "right"] {
+        let (default, limit) = match side {
+            "left" => (start, end - jaws.length * 2.0 - distance_x),
+            _ => (end - jaws.length, left + jaws.length + distance_x),
+        };
+
+        let works = blocking(default);
+        if works.is_empty() {
+            left = default;
+            continue;
+        }
+
+        for work in works {
+            issues.push(Issue::warning(format!(
+                "Cut {} : {} at {:.1} mm is in the {side} clamp zone",
+                cut.number,
+                label(work),
+                work.x
+            )));
+        }
+
+        match positions(default, limit).find(|position| blocking(*position).is_empty()) {
+            Some(position) => {
+                issues.push(Issue::warning(format!(
+                    "Cut {} : move the {side} clamp to {:.1} mm",
+                    cut.number,
+                    position - jaws.start
+                )));
+                left = position;
+            }
+
+            None => issues.push(Issue::blocking(format!(
+                "Cut {} : no free position for the {side} clamp",
+                cut.number
+            ))),
+        }
+    }
+
+    issues
+}
+
+/// Checks the clamp zones of an Elumatec document. Documents without clamp
+/// geometry (`BFT0*`), or whose clamps are not positioned by the machine,
+/// are not checked.
+pub(crate) fn check(document: &str) -> Result<Vec<Issue>> {
+    let safety = Safety::from_tags(&read_tags(document)?);
+    if safety.mode == 0 {
+        return Ok(vec![]);
+    }
+
+    Ok(read_cuts(document)?
+        .iter()
+        .flat_map(|cut| check_cut(cut, &safety))
+        .collect())
+}
+
+/// Checks the clamp zones of named documents. Clamps to move are reported as
+/// warnings, parts that cannot be clamped fail.
+pub(crate) fn verify(documents: &[(String, String)]) -> Result<()> {
+    let mut blocking = vec![];
+    for (name, document) in documents {
+        for issue in check(document)? {
+            if issue.blocking {
+                blocking.push(format!("{name} : {}", issue.message));
+            } else {
+                eprintln!("{name} : {}", issue.message);
+            }
+        }
+    }
+
+    if !blocking.is_empty() {
+        return Err(anyhow!(
+            "Parts that cannot be clamped :\n{}",
+            blocking.join("\n")
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::Jaws;
+
+    fn cut(length: f32, works: Vec<Work>) -> Cut {
+        Cut {
+            number: 1,
+            part: 1,
+            length,
+            left: (90.0, 90.0),
+            right: (45.0, 90.0),
+            bar: Bar {
+                width: 60.0,
+                height: 100.0,
+                jaws: Some(Jaws {
+                    start: -25.0,
+                    length: 50.0,
+                    height: 60.0,
+                }),
+                ..Default::default()
+            },
+            works,
+        }
+    }
+
+    fn drilling(face: Face, x: f32, y: f32) -> Work {
+        Work {
+            kind: "B".to_owned(),
+            number: 1,
+            face,
+            x,
+            y,
+            diameter: 10.0,
+            depth: 0.0,
+            width: 0.0,
+            length: 0.0,
+            angle: 0.0,
+            tool: String::new(),
+            comment: String::new(),
+        }
+    }
+
+    fn messages(issues: &[Issue]) -> Vec<&str> {
+        issues.iter().map(|issue| issue.message.as_str()).collect()
+    }
+
+    const SAFETY: Safety = Safety {
+        clamp: (5.0, 5.0, 5.0),
+        collision: 5.0,
+        mode: 1,
+    };
+
+    #[test]
+    fn flag_short_parts() {
+        // 100 mm lost in the right mitre
+        assert_eq!(
+            check_cut(&cut(180.0, vec![]), &SAFETY),
+            [Issue::blocking(
+                "Cut 1 : part too short to be clamped, 80.0 mm of full section for 105.0 mm needed"
+                    .to_owned()
+            )]
+        );
+        assert!(check_cut(&cut(300.0, vec![]), &SAFETY).is_empty());
+    }
+
+    #[test]
+    fn move_clamps_away_from_works() {
+        let works = vec![
+            // Below the jaws, in both clamp zones
+            drilling(Face::Front, 30.0, 80.0),
+            drilling(Face::Back, 1880.0, 80.0),
+            // Above the jaws
+            drilling(Face::Front, 30.0, 20.0),
+            drilling(Face::Top, 30.0, 30.0),
+        ];
+
+        let issues = check_cut(&cut(2000.0, works), &SAFETY);
+        assert!(issues.iter().all(|issue| !issue.blocking));
+        assert_eq!(
+            messages(&issues),
+            [
+                "Cut 1 : work 1 at 30.0 mm is in the left clamp zone",
+                "Cut 1 : move the left clamp to 70.0 mm",
+                "Cut 1 : work 1 at 1880.0 mm is in the right clamp zone",
+                "Cut 1 : move the right clamp to 1840.0 mm",
+            ]
+        );
+    }
+
+    #[test]
+    fn flag_clamps_without_free_position() {
+        let works = [30.0, 90.0, 150.0]
+            .into_iter()
+            .map(|x| drilling(Face::Front, x, 80.0))
+            .collect();
+
+        let blocking = check_cut(&cut(300.0, works), &SAFETY)
+            .into_iter()
+            .filter(|issue| issue.blocking)
+            .map(|issue| issue.message)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            blocking,
+            [
+                "Cut 1 : no free position for the left clamp",
+                "Cut 1 : no free position for the right clamp",
+            ]
+        );
+    }
+
+    #[test]
+    fn check_example() {
+        let issues = check(include_str!("../../examples/elumatec.ncw")).unwrap();
+
+        assert!(issues.iter().all(|issue| !issue.blocking));
+        assert_eq!(
+            messages(&issues),
+            [
+                "Cut 1 : work 5 `GU1 S1 KOP` at 42.6 mm is in the left clamp zone",
+                "Cut 1 : work 6 `GU1 S1 KOP` at 42.6 mm is in the left clamp zone",
+                "Cut 1 : move the left clamp to 92.0 mm",
+            ]
+        );
+    }
+}
@@ -0,0 +1,450 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Debug},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+pub use crate::cover::{FixtureMode, Fixtures};
+pub use crate::template::Templates;
+use crate::{BomFormat, Machine, Split};
+
+/// Project configuration file, read from the working directory.
+pub const PROJECT_FILE: &str = "machining-transpiler.toml";
+
+pub(crate) const APP: &str = "machining-transpiler";
+
+/// Settings of the transpiler. System, user and project files are merged in
+/// that order, then the environment ; command line flags are applied last by
+/// the caller.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub api: Api,
+    pub templates: Templates,
+    /// Machine profiles by name, looked up before the `machines` template.
+    pub machines: BTreeMap<String, Machine>,
+    pub output: Output,
+    pub csv: Csv,
+    pub cache: Cache,
+    pub watch: Watch,
+}
+
+/// Cover API.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Api {
+    pub url: Option<String>,
+    pub auth: Option<Auth>,
+
+    /// Timeout of each request, in seconds.
+    pub timeout: Option<u64>,
+
+    /// Timeout of the connection to the API, in seconds.
+    pub connect_timeout: Option<u64>,
+
+    /// Retries of requests failing on the network or the server side.
+    pub retries: Option<u32>,
+
+    /// Responses recorded from the API, or replayed instead of it.
+    pub fixtures: Option<Fixtures>,
+}
+
+/// Credentials of the API. Secrets are hidden from the `Debug` output.
+#[derive(Clone, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Auth {
+    /// Static token.
+    Bearer { token: String },
+
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+
+    /// Client credentials grant, tokens being requested from `token_url`.
+    OAuth2 {
+        token_url: String,
+        client_id: String,
+        client_secret: Option<String>,
+        scope: Option<String>,
+    },
+}
+
+/// Defaults of the output flags.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Output {
+    pub directory: Option<String>,
+    pub split: Option<Split>,
+    pub name_pattern: Option<String>,
+    pub bom: Option<BomFormat>,
+}
+
+/// Layout of the CSV cut list.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Csv {
+    pub columns: Option<Vec<String>>,
+    pub separator: Option<String>,
+    pub decimal: Option<String>,
+}
+
+/// Project data kept for `--offline` exports.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Cache {
+    /// Defaults to the user cache directory.
+    pub directory: Option<PathBuf>,
+}
+
+/// Projects exported by `watch-api`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Watch {
+    /// Provider the projects are exported for.
+    pub vendor: Option<String>,
+
+    /// Status of the projects to export, `READY_FOR_PRODUCTION` by default.
+    pub status: Option<String>,
+
+    /// Seconds between two polls of the API, 60 by default.
+    pub interval: Option<u64>,
+
+    /// Folder the exported files are moved to once complete.
+    pub spool: Option<PathBuf>,
+
+    /// File of the projects already exported, `.watch-state.json` of the
+    /// spool folder by default.
+    pub state: Option<PathBuf>,
+}
+
+impl Config {
+    /// Reads the configuration files, then the environment. `file` replaces
+    /// the project file of the working directory.
+    pub fn load(file: Option<&Path>) -> Result<Self> {
+        let mut config = Config::default();
+
+        for path in [system_file(), user_file()].into_iter().flatten() {
+            if path.is_file() {
+                config.merge(Self::read(&path)?);
+            }
+        }
+
+        match file {
+            Some(path) => config.merge(Self::read(path)?),
+            None if Path::new(PROJECT_FILE).is_file() => {
+                config.merge(Self::read(Path::new(PROJECT_FILE))?)
+            }
+            None => {}
+        }
+
+        config.apply_env(|name| std::env::var(name).ok());
+
+        Ok(config)
+    }
+
+    /// Reads a single configuration file.
+    pub fn read(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read configuration file {}", path.display()))?;
+        let mut config: Config = toml::from_str(&contents)
+            .with_context(|| format!("Invalid configuration file {}", path.display()))?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        config.cache.directory = config.cache.directory.map(|folder| base.join(folder));
+        config.watch.spool = config.watch.spool.map(|folder| base.join(folder));
+        config.watch.state = config.watch.state.map(|file| base.join(file));
+        if let Some(fixtures) = &mut config.api.fixtures {
+            fixtures.directory = base.join(&fixtures.directory);
+        }
+        config.templates.paths = config
+            .templates
+            .paths
+            .into_iter()
+            .map(|folder| base.join(folder))
+            .collect();
+
+        Ok(config)
+    }
+
+    /// Overrides settings with those set in `other`. Its template folders are
+    /// searched first, its machines replace those of the same name.
+    pub fn merge(&mut self, other: Config) {
+        set(&mut self.api.url, other.api.url);
+        set(&mut self.api.auth, other.api.auth);
+        set(&mut self.api.timeout, other.api.timeout);
+        set(&mut self.api.connect_timeout, other.api.connect_timeout);
+        set(&mut self.api.retries, other.api.retries);
+        set(&mut self.api.fixtures, other.api.fixtures);
+
+        self.templates.paths.splice(0..0, other.templates.paths);
+        self.machines.extend(other.machines);
+
+        set(&mut self.output.directory, other.output.directory);
+        set(&mut self.output.split, other.output.split);
+        set(&mut self.output.name_pattern, other.output.name_pattern);
+        set(&mut self.output.bom, other.output.bom);
+
+        set(&mut self.csv.columns, other.csv.columns);
+        set(&mut self.csv.separator, other.csv.separator);
+        set(&mut self.csv.decimal, other.csv.decimal);
+
+        set(&mut self.cache.directory, other.cache.directory);
+
+        set(&mut self.watch.vendor, other.watch.vendor);
+        set(&mut self.watch.status, other.watch.status);
+        set(&mut self.watch.interval, other.watch.interval);
+        set(&mut self.watch.spool, other.watch.spool);
+        set(&mut self.watch.state, other.watch.state);
+    }
+
+    /// Applies the `BASE_URL`, `COVER_*`, `TEMPLATE_PATH` and `CSV_*`
+    /// variables given by `var`. Empty variables are ignored.
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) {
+        let var = |name: &str| var(name).filter(|value| !value.trim().is_empty());
+
+        let mut env = Config::default();
+        env.api.url = var("BASE_URL");
+        env.api.fixtures = [
+            ("COVER_RECORD", FixtureMode::Record),
+            ("COVER_REPLAY", FixtureMode::Replay),
+        ]
+        .into_iter()
+        .find_map(|(name, mode)| {
+            Some(Fixtures {
+                directory: PathBuf::from(var(name)?),
+                mode,
+            })
+        });
+        env.api.auth = if let Some(token) = var("COVER_TOKEN") {
+            Some(Auth::Bearer { token })
+        } else if let Some(username) = var("COVER_USERNAME") {
+            Some(Auth::Basic {
+                username,
+                password: None,
+            })
+        } else if let (Some(token_url), Some(client_id)) =
+            (var("COVER_TOKEN_URL"), var("COVER_CLIENT_ID"))
+        {
+            Some(Auth::OAuth2 {
+                token_url,
+                client_id,
+                client_secret: None,
+                scope: var("COVER_SCOPE"),
+            })
+        } else {
+            None
+        };
+        if let Some(paths) = var("TEMPLATE_PATH") {
+            env.templates.paths = std::env::split_paths(&paths).collect();
+        }
+        env.csv.columns =
+            var("CSV_COLUMNS").map(|columns| columns.split(',').map(str::to_owned).collect());
+        env.csv.separator = var("CSV_SEPARATOR");
+        env.csv.decimal = var("CSV_DECIMAL");
+
+        self.merge(env);
+
+        // Secrets complete the credentials of any layer, so that files need
+        // not hold them
+        match &mut self.api.auth {
+            Some(Auth::Basic { password, .. }) => set(password, var("COVER_PASSWORD")),
+            Some(Auth::OAuth2 { client_secret, .. }) => {
+                set(client_secret, var("COVER_CLIENT_SECRET"))
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Debug for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const HIDDEN: &str = "***";
+
+        match self {
+            Self::Bearer { .. } => f.debug_struct("Bearer").field("token", &HIDDEN).finish(),
+            Self::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .field("password", &HIDDEN)
+                .finish(),
+            Self::OAuth2 {
+                token_url,
+                client_id,
+                scope,
+                ..
+            } => f
+                .debug_struct("OAuth2")
+                .field("token_url", token_url)
+                .field("client_id", client_id)
+                .field("client_secret", &HIDDEN)
+                .field("scope", scope)
+                .finish(),
+        }
+    }
+}
+
+/// Overrides `setting` when `value` is set.
+fn set<T>(setting: &mut Option<T>, value: Option<T>) {
+    if value.is_some() {
+        *setting = value;
+    }
+}
+
+/// `config.toml` shared by the users of the computer.
+fn system_file() -> Option<PathBuf> {
+    let folder = if cfg!(windows) {
+        PathBuf::from(std::env::var_os("PROGRAMDATA")?)
+    } else {
+        PathBuf::from("/etc")
+    };
+
+    Some(folder.join(APP).join("config.toml"))
+}
+
+/// Configuration directory of the current user.
+pub(crate) fn user_dir() -> Option<PathBuf> {
+    let folder = if cfg!(windows) {
+        PathBuf::from(std::env::var_os("APPDATA")?)
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| Some(Path::new(&std::env::var_os("HOME")?).join(".config")))?
+    };
+
+    Some(folder.join(APP))
+}
+
+/// `config.toml` of the current user.
+fn user_file() -> Option<PathBuf> {
+    Some(user_dir()?.join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn override_lower_layers() {
+        let mut config: Config = toml::from_str(
+            r#"
+            api.url = "http://system"
+            templates.paths = ["/usr/share/templates"]
+
+            [output]
+            split = "view"
+            bom = "csv"
+            "#,
+        )
+        .unwrap();
+
+        config.merge(
+            toml::from_str(
+                r#"
+                api = { url = "http://project", auth = { type = "bearer", token = "secret" } }
+                templates.paths = ["templates"]
+                output.split = "profile"
+
+                [machines.small]
+                max_length = 2000
+                tools = ["ZAAG180"]
+                "#,
+            )
+            .unwrap(),
+        );
+
+        config.apply_env(|name| match name {
+            "BASE_URL" => Some("http://env".to_owned()),
+            "CSV_COLUMNS" => Some("profile,length".to_owned()),
+            "CSV_SEPARATOR" => Some(String::new()),
+            _ => None,
+        });
+
+        assert_eq!(config.api.url.as_deref(), Some("http://env"));
+        assert_eq!(
+            config.api.auth,
+            Some(Auth::Bearer {
+                token: "secret".to_owned()
+            })
+        );
+        assert_eq!(
+            config.templates.paths,
+            [
+                PathBuf::from("templates"),
+                PathBuf::from("/usr/share/templates")
+            ]
+        );
+        assert_eq!(config.output.split, Some(Split::Profile));
+        assert_eq!(config.output.bom, Some(BomFormat::Csv));
+        assert_eq!(config.machines["small"].template, "elumatec");
+        assert_eq!(config.machines["small"].max_length, Some(2000.0));
+        assert_eq!(
+            config.csv.columns,
+            Some(vec!["profile".to_owned(), "length".to_owned()])
+        );
+        assert_eq!(config.csv.separator, None);
+    }
+
+    #[test]
+    fn read_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let folder = temp.path();
+        let path = folder.join(PROJECT_FILE);
+
+        fs::write(&path, "templates.paths = [\"templates\"]\n").unwrap();
+        let config = Config::read(&path).unwrap();
+        assert_eq!(config.templates.paths, [folder.join("templates")]);
+
+        fs::write(&path, "[api]\nbase_url = \"http://localhost\"\n").unwrap();
+        let err = Config::read(&path).unwrap_err();
+        assert!(format!("{err:#}").contains("unknown field `base_url`"));
+    }
+
+    #[test]
+    fn read_credentials_from_env() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [api.auth]
+            type = "oauth2"
+            token_url = "http://auth/token"
+            client_id = "transpiler"
+            "#,
+        )
+        .unwrap();
+
+        config.apply_env(|name| match name {
+            "COVER_CLIENT_SECRET" => Some("s3cr3t".to_owned()),
+            _ => None,
+        });
+
+        assert_eq!(
+            config.api.auth,
+            Some(Auth::OAuth2 {
+                token_url: "http://auth/token".to_owned(),
+                client_id: "transpiler".to_owned(),
+                client_secret: Some("s3cr3t".to_owned()),
+                scope: None,
+            })
+        );
+        assert!(!format!("{config:?}").contains("s3cr3t"));
+
+        config.apply_env(|name| match name {
+            "COVER_USERNAME" => Some("operator".to_owned()),
+            "COVER_PASSWORD" => Some("s3cr3t".to_owned()),
+            _ => None,
+        });
+
+        assert_eq!(
+            config.api.auth,
+            Some(Auth::Basic {
+                username: "operator".to_owned(),
+                password: Some("s3cr3t".to_owned()),
+            })
+        );
+        assert!(!format!("{config:?}").contains("s3cr3t"));
+    }
+}
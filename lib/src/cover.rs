@@ -0,0 +1,935 @@
+use std::{
+    error::Error,
+    fmt::{self, Debug, Display},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::{
+    blocking::{
+        multipart::{Form, Part},
+        Client, RequestBuilder,
+    },
+    header::{ETAG, IF_NONE_MATCH, RETRY_AFTER},
+    StatusCode, Url,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+pub use self::fixture::{FixtureMode, Fixtures};
+use crate::config::{Api, Auth};
+
+mod fixture;
+
+/// Timeout of a request when `api.timeout` is not set, in seconds.
+const DEFAULT_TIMEOUT: u64 = 30;
+
+/// Projects requested per page when listing them.
+const PAGE_SIZE: usize = 100;
+
+/// Retries of a failed request when `api.retries` is not set.
+const DEFAULT_RETRIES: u32 = 3;
+
+/// Wait before the first retry, doubled for each following one.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Longest wait between two retries, whatever the server asks for.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// OAuth2 tokens are renewed this long before they expire.
+const TOKEN_MARGIN: Duration = Duration::from_secs(30);
+
+/// Endpoint generated programs are uploaded to.
+const DOCUMENT_PATH: &str = "/document";
+
+/// Client of the Cover API. Connections are pooled, a single client is meant
+/// to serve every request of a run.
+#[derive(Clone)]
+pub struct CoverClient {
+    client: Client,
+    url: Url,
+    auth: Option<Auth>,
+    retries: u32,
+    retry_delay: Duration,
+
+    fixtures: Option<Fixtures>,
+
+    /// OAuth2 access token and its expiry, shared by the clones.
+    token: Arc<Mutex<Option<(String, Instant)>>>,
+}
+
+/// Response of the API, read in full.
+struct Reply {
+    status: StatusCode,
+    etag: Option<String>,
+    body: Vec<u8>,
+}
+
+/// Request to the Cover API, or to its token endpoint, that failed after
+/// every retry.
+#[derive(Debug)]
+pub struct ApiError {
+    /// URL requested, without its query.
+    pub endpoint: String,
+
+    /// Status of the response, unset when no response came.
+    pub status: Option<StatusCode>,
+    pub reason: String,
+}
+
+/// Project, as returned by the search endpoints.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Project {
+    pub id: Option<u64>,
+    pub uuid: String,
+    pub name: String,
+
+    /// Fields the transpiler does not read.
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+impl Project {
+    /// Last update, or creation, date of the project.
+    pub fn date(&self) -> Option<String> {
+        self.updated()
+            .or_else(|| self.field(&["createdAt", "creationDate"]))
+    }
+
+    /// Last update date of the project, which changes with its data unlike
+    /// the creation date.
+    pub fn updated(&self) -> Option<String> {
+        self.field(&["date", "updatedAt", "updateDate"])
+    }
+
+    /// Workflow status of the project.
+    pub fn status(&self) -> Option<String> {
+        self.field(&["status", "state"])
+    }
+
+    /// First of `keys` set to a string or a number among the other fields.
+    fn field(&self, keys: &[&str]) -> Option<String> {
+        keys.iter().find_map(|key| match self.other.get(*key)? {
+            Value::String(value) => Some(value.clone()),
+            Value::Number(value) => Some(value.to_string()),
+            _ => None,
+        })
+    }
+}
+
+/// Page of the project list.
+#[derive(Clone, Debug, Default)]
+pub struct ProjectPage {
+    pub projects: Vec<Project>,
+
+    /// Projects and pages in all, unknown when the API does not page the list.
+    pub total: Option<usize>,
+    pub pages: Option<usize>,
+}
+
+/// Document data of a project, which parts, bills of materials and macros
+/// are read from.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct ProjectData {
+    #[serde(rename = "structureViews")]
+    pub structure_views: Option<Vec<Value>>,
+
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+/// Record uploaded with a generated program, telling how it was produced.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentMetadata {
+    pub project_uuid: String,
+    pub vendor: String,
+    pub exporter_version: String,
+
+    /// Template the program was rendered from, if any, and the SHA-256 of
+    /// its contents and of those it includes or extends.
+    pub template: Option<String>,
+    pub template_hash: Option<String>,
+
+    /// When the program was generated, in seconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+impl CoverClient {
+    pub fn new(api: &Api) -> Result<Self> {
+        let url = api.url.as_deref().ok_or(anyhow!(
+            "Cover API URL is not set : use `api.url`, `BASE_URL` or `--api-url`"
+        ))?;
+        let url = Url::parse(url).with_context(|| format!("Invalid Cover API URL `{url}`"))?;
+
+        if let Some(Auth::OAuth2 {
+            client_secret: None,
+            ..
+        }) = api.auth
+        {
+            return Err(anyhow!(
+                "OAuth2 client secret is not set : use `api.auth.client_secret` or `COVER_CLIENT_SECRET`"
+            ));
+        }
+
+        let timeout = Duration::from_secs(api.timeout.unwrap_or(DEFAULT_TIMEOUT));
+        let client = Client::builder()
+            .timeout(timeout)
+            .connect_timeout(api.connect_timeout.map_or(timeout, Duration::from_secs))
+            .build()?;
+
+        Ok(Self {
+            client,
+            url,
+            auth: api.auth.clone(),
+            retries: api.retries.unwrap_or(DEFAULT_RETRIES),
+            retry_delay: RETRY_DELAY,
+            fixtures: api.fixtures.clone(),
+            token: Default::default(),
+        })
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Sends an authenticated GET request to `path` of the API, `None` being
+    /// returned when nothing is found. Connection failures, timeouts, `5xx`
+    /// and `429` responses are retried with an exponential backoff, and an
+    /// OAuth2 token the API rejects is renewed once.
+    fn get(&self, path: &str, query: &[(&str, &str)]) -> Result<Option<Reply>> {
+        self.get_if_changed(path, query, None)
+    }
+
+    /// Same as `get`, the response being `304 Not Modified` when the resource
+    /// still matches `etag`. Responses are recorded to, or replayed from, the
+    /// fixtures if any.
+    fn get_if_changed(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+        etag: Option<&str>,
+    ) -> Result<Option<Reply>> {
+        let url = self.url.join(path)?;
+        let fail = |status, reason| ApiError {
+            endpoint: url.to_string(),
+            status,
+            reason,
+        };
+
+        let reply = match &self.fixtures {
+            Some(fixtures) if fixtures.mode == FixtureMode::Replay => fixtures.read(path, query)?,
+            _ => self.send(
+                || {
+                    let request = self.client.get(url.clone()).query(query);
+                    Ok(match etag {
+                        Some(etag) => request.header(IF_NONE_MATCH, etag),
+                        None => request,
+                    })
+                },
+                &fail,
+            )?,
+        };
+
+        if let Some(fixtures) = &self.fixtures {
+            if fixtures.mode == FixtureMode::Record {
+                fixtures.write(path, query, &reply)?;
+            }
+        }
+
+        match reply.status {
+            status if status.is_success() || status == StatusCode::NOT_MODIFIED => Ok(Some(reply)),
+            StatusCode::NOT_FOUND => Ok(None),
+            status => Err(fail(Some(status), String::new()).into()),
+        }
+    }
+
+    /// Response to the request `build` creates, once retries are over. It is
+    /// created again for each attempt. Only idempotent requests, such as GET,
+    /// are retried : the others may fail after the server processed them, and
+    /// are sent once.
+    fn send(
+        &self,
+        build: impl Fn() -> Result<RequestBuilder>,
+        fail: &impl Fn(Option<StatusCode>, String) -> ApiError,
+    ) -> Result<Reply> {
+        let mut attempt = 0;
+        let mut renew = false;
+        loop {
+            let request = self.authorize(build()?, renew)?.build()?;
+            let idempotent = request.method().is_idempotent();
+
+            let (status, reason, wait) = match self.client.execute(request) {
+                Ok(res)
+                    if res.status() == StatusCode::UNAUTHORIZED
+                        && matches!(self.auth, Some(Auth::OAuth2 { .. }))
+                        && !renew =>
+                {
+                    renew = true;
+                    continue;
+                }
+                Ok(res)
+                    if res.status().is_server_error()
+                        || res.status() == StatusCode::TOO_MANY_REQUESTS =>
+                {
+                    let wait = res
+                        .headers()
+                        .get(RETRY_AFTER)
+                        .and_then(|value| value.to_str().ok()?.parse().ok())
+                        .map(Duration::from_secs);
+                    (Some(res.status()), String::new(), wait)
+                }
+                Ok(res) => {
+                    return Ok(Reply {
+                        status: res.status(),
+                        etag: res
+                            .headers()
+                            .get(ETAG)
+                            .and_then(|etag| etag.to_str().ok())
+                            .map(str::to_owned),
+                        body: res
+                            .bytes()
+                            .map_err(|err| fail(None, reason(&err)))?
+                            .to_vec(),
+                    })
+                }
+                Err(err) => (None, reason(&err), None),
+            };
+
+            renew = false;
+            if !idempotent || attempt >= self.retries {
+                return Err(fail(status, reason).into());
+            }
+
+            let delay = wait
+                .unwrap_or(self.retry_delay * 2u32.pow(attempt))
+                .min(MAX_RETRY_DELAY);
+            eprintln!(
+                "{}, retrying in {:.1} s",
+                fail(status, reason),
+                delay.as_secs_f32()
+            );
+
+            thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+
+    fn authorize(&self, request: RequestBuilder, renew: bool) -> Result<RequestBuilder> {
+        Ok(match &self.auth {
+            Some(Auth::Bearer { token }) => request.bearer_auth(token),
+            Some(Auth::Basic { username, password }) => {
+                request.basic_auth(username, password.as_ref())
+            }
+            Some(Auth::OAuth2 { .. }) => request.bearer_auth(self.access_token(renew)?),
+            None => request,
+        })
+    }
+
+    /// OAuth2 access token, requested again when it is about to expire or
+    /// when `renew` is set.
+    fn access_token(&self, renew: bool) -> Result<String> {
+        let Some(Auth::OAuth2 {
+            token_url,
+            client_id,
+            client_secret,
+            scope,
+        }) = &self.auth
+        else {
+            return Err(anyhow!("Cover API credentials are not OAuth2 ones"));
+        };
+
+        let mut token = self.token.lock().unwrap();
+        if let Some((access_token, expiry)) = token.as_ref() {
+            if !renew && Instant::now() + TOKEN_MARGIN < *expiry {
+                return Ok(access_token.clone());
+            }
+        }
+
+        #[derive(Deserialize)]
+        struct Grant {
+            access_token: String,
+            expires_in: Option<u64>,
+        }
+
+        let mut form = vec![("grant_type", "client_credentials")];
+        if let Some(scope) = scope {
+            form.push(("scope", scope));
+        }
+
+        let fail = |status, reason| ApiError {
+            endpoint: token_url.clone(),
+            status,
+            reason,
+        };
+
+        let res = self
+            .client
+            .post(token_url)
+            .basic_auth(client_id, client_secret.as_ref())
+            .form(&form)
+            .send()
+            .map_err(|err| fail(None, reason(&err)))?;
+
+        if !res.status().is_success() {
+            return Err(fail(Some(res.status()), "client credentials refused".to_owned()).into());
+        }
+
+        let grant: Grant = res
+            .json()
+            .with_context(|| format!("Invalid response of Cover token endpoint {token_url}"))?;
+        let expiry = Instant::now() + Duration::from_secs(grant.expires_in.unwrap_or(3600));
+        *token = Some((grant.access_token.clone(), expiry));
+
+        Ok(grant.access_token)
+    }
+
+    /// Every project, read page by page.
+    pub fn projects(&self) -> Result<Vec<Project>> {
+        let mut projects = vec![];
+
+        for page in 0.. {
+            let list = self.project_page(page, PAGE_SIZE)?;
+            let last = list.projects.is_empty() || page + 1 >= list.pages.unwrap_or_default();
+            projects.extend(list.projects);
+
+            if last {
+                break;
+            }
+        }
+
+        Ok(projects)
+    }
+
+    /// Page `page` of the project list, counted from 0, of `size` projects.
+    /// Plain lists and paged collections, projects being under `_embedded`,
+    /// are both accepted : plain lists hold every project.
+    pub fn project_page(&self, page: usize, size: usize) -> Result<ProjectPage> {
+        let Some(res) = self.get(
+            "/project",
+            &[("page", &page.to_string()), ("size", &size.to_string())],
+        )?
+        else {
+            return Ok(ProjectPage::default());
+        };
+        let json: Value = res.json().context("Invalid response of `/project`")?;
+
+        let list = match &json {
+            Value::Array(_) => json.clone(),
+            _ => json["_embedded"]
+                .as_object()
+                .and_then(|embedded| embedded.values().find(|value| value.is_array()))
+                .cloned()
+                .unwrap_or_default(),
+        };
+        let count = |key: &str| json["page"][key].as_u64().map(|count| count as usize);
+
+        Ok(ProjectPage {
+            projects: match list {
+                Value::Null => vec![],
+                list => serde_json::from_value(list).context("Invalid response of `/project`")?,
+            },
+            total: count("totalElements"),
+            pages: count("totalPages"),
+        })
+    }
+
+    pub fn find_by_id(&self, id: u64) -> Result<Option<Project>> {
+        self.get("/project/search/findById", &[("id", &id.to_string())])?
+            .map(|res| res.json())
+            .transpose()
+            .context("Invalid response of `/project/search/findById`")
+    }
+
+    /// Projects whose name matches `name`, as the API searches them.
+    pub fn find_by_name(&self, name: &str) -> Result<Vec<Project>> {
+        let Some(res) = self.get("/project/search/findByName", &[("name", name)])? else {
+            return Ok(vec![]);
+        };
+
+        res.json()
+            .context("Invalid response of `/project/search/findByName`")
+    }
+
+    pub fn find_by_uuid(&self, uuid: &str) -> Result<Option<Project>> {
+        self.get("/project/search/findByUuid", &[("uuid", uuid)])?
+            .map(|res| res.json())
+            .transpose()
+            .context("Invalid response of `/project/search/findByUuid`")
+    }
+
+    pub fn project_data(&self, project_uuid: &str) -> Result<ProjectData> {
+        self.project_data_if_changed(project_uuid, None)?
+            .map(|(data, _)| data)
+            .ok_or(anyhow!("No data found for project {project_uuid}"))
+    }
+
+    /// Data of a project and its ETag, unless it still matches `etag`.
+    pub fn project_data_if_changed(
+        &self,
+        project_uuid: &str,
+        etag: Option<&str>,
+    ) -> Result<Option<(ProjectData, Option<String>)>> {
+        let res = self
+            .get_if_changed(
+                "/documentData/search/findProjectDataByProjectUuid",
+                &[("projectUuid", project_uuid)],
+                etag,
+            )?
+            .ok_or(anyhow!("No data found for project {project_uuid}"))?;
+
+        if res.status == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+
+        let data = res
+            .json()
+            .context("Invalid response of `/documentData/search/findProjectDataByProjectUuid`")?;
+
+        Ok(Some((data, res.etag)))
+    }
+
+    /// Uploads every program of `files`, returning the documents created.
+    /// Fails when there is none, an export writing nothing having nothing to
+    /// send.
+    pub fn upload_files(
+        &self,
+        files: &[PathBuf],
+        metadata: &DocumentMetadata,
+    ) -> Result<Vec<Value>> {
+        if files.is_empty() {
+            return Err(anyhow!(
+                "No file to upload : the export of project {} wrote no program",
+                metadata.project_uuid
+            ));
+        }
+
+        files
+            .iter()
+            .map(|file| self.upload(file, metadata))
+            .collect()
+    }
+
+    /// Uploads the program `file` to the document endpoint, as a multipart
+    /// form holding the `projectUuid`, the `metadata` as JSON and the `file`.
+    /// Returns the document created, if the API describes it.
+    pub fn upload(&self, file: &Path, metadata: &DocumentMetadata) -> Result<Value> {
+        if let Some(Fixtures {
+            mode: FixtureMode::Replay,
+            ..
+        }) = &self.fixtures
+        {
+            return Err(anyhow!(
+                "Uploads are not replayed from fixtures : unset `api.fixtures` or `COVER_REPLAY`"
+            ));
+        }
+
+        let contents =
+            fs::read(file).with_context(|| format!("Unable to read {}", file.display()))?;
+        let file_name = file
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let json = serde_json::to_string(metadata)?;
+
+        let url = self.url.join(DOCUMENT_PATH)?;
+        let fail = |status, reason| ApiError {
+            endpoint: url.to_string(),
+            status,
+            reason,
+        };
+
+        let reply = self.send(
+            || {
+                let form = Form::new()
+                    .text("projectUuid", metadata.project_uuid.clone())
+                    .part(
+                        "metadata",
+                        Part::text(json.clone()).mime_str("application/json")?,
+                    )
+                    .part(
+                        "file",
+                        Part::bytes(contents.clone())
+                            .file_name(file_name.clone())
+                            .mime_str("application/octet-stream")?,
+                    );
+
+                Ok(self.client.post(url.clone()).multipart(form))
+            },
+            &fail,
+        )?;
+
+        if !reply.status.is_success() {
+            return Err(fail(Some(reply.status), String::new()).into());
+        }
+
+        Ok(reply.json().unwrap_or_default())
+    }
+}
+
+impl Reply {
+    fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.status {
+            Some(status) => write!(
+                f,
+                "Cover API request to {} failed : {status}",
+                self.endpoint
+            )?,
+            None => write!(f, "Unable to reach Cover API at {}", self.endpoint)?,
+        }
+
+        match (self.status, self.reason.is_empty()) {
+            (_, true) => Ok(()),
+            (Some(_), false) => write!(f, " ({})", self.reason),
+            (None, false) => write!(f, " : {}", self.reason),
+        }
+    }
+}
+
+impl Error for ApiError {}
+
+/// Innermost cause of a failed request, such as a refused connection or a
+/// failed DNS lookup.
+fn reason(err: &reqwest::Error) -> String {
+    if err.is_timeout() {
+        return "request timed out".to_owned();
+    }
+
+    let mut source: &dyn Error = err;
+    while let Some(next) = source.source() {
+        source = next;
+    }
+
+    source.to_string()
+}
+
+/// Secrets and tokens are never printed.
+impl Debug for CoverClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CoverClient")
+            .field("url", &self.url.as_str())
+            .field("auth", &self.auth)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread::{self, JoinHandle},
+    };
+
+    use super::*;
+
+    /// Serves `responses` in order, one per connection, and returns the
+    /// requests received.
+    fn serve(responses: Vec<(u16, &'static str)>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let server = thread::spawn(move || {
+            responses
+                .into_iter()
+                .map(|(status, body)| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                    let mut request = String::new();
+                    while reader.read_line(&mut request).unwrap() > 2 {}
+
+                    let length = request
+                        .lines()
+                        .find_map(|line| {
+                            let (name, value) = line.split_once(':')?;
+                            name.eq_ignore_ascii_case("content-length")
+                                .then(|| value.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or_default();
+                    let mut content = vec![0; length];
+                    reader.read_exact(&mut content).unwrap();
+                    request += &String::from_utf8(content).unwrap();
+
+                    write!(
+                        stream,
+                        "HTTP/1.1 {status} \r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                    .unwrap();
+
+                    request
+                })
+                .collect()
+        });
+
+        (url, server)
+    }
+
+    fn header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+        request.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.eq_ignore_ascii_case(name).then_some(value.trim())
+        })
+    }
+
+    #[test]
+    fn renew_oauth2_token() {
+        let project = r#"{ "id": 8, "uuid": "p1", "name": "import" }"#;
+        let (url, server) = serve(vec![
+            (200, r#"{ "access_token": "first", "expires_in": 3600 }"#),
+            (200, project),
+            (200, project),
+            (401, ""),
+            (200, r#"{ "access_token": "second" }"#),
+            (200, project),
+        ]);
+
+        let client = CoverClient::new(&Api {
+            url: Some(url.clone()),
+            auth: Some(Auth::OAuth2 {
+                token_url: format!("{url}/oauth/token"),
+                client_id: "transpiler".to_owned(),
+                client_secret: Some("s3cr3t".to_owned()),
+                scope: Some("projects".to_owned()),
+            }),
+            ..Default::default()
+        })
+        .unwrap();
+
+        for _ in 0..3 {
+            assert_eq!(client.find_by_uuid("p1").unwrap().unwrap().name, "import");
+        }
+
+        let requests = server.join().unwrap();
+        for (index, token) in [(0, None), (1, Some("first")), (2, Some("first"))]
+            .into_iter()
+            .chain([(3, Some("first")), (4, None), (5, Some("second"))])
+        {
+            let request = &requests[index];
+            match token {
+                Some(token) => {
+                    assert!(request.starts_with("GET /project/search/findByUuid?uuid=p1 "));
+                    assert_eq!(
+                        header(request, "authorization"),
+                        Some(format!("Bearer {token}").as_str())
+                    );
+                }
+                None => {
+                    assert!(request.starts_with("POST /oauth/token "));
+                    assert_eq!(
+                        header(request, "authorization"),
+                        Some("Basic dHJhbnNwaWxlcjpzM2NyM3Q=")
+                    );
+                    assert!(request.ends_with("grant_type=client_credentials&scope=projects"));
+                }
+            }
+        }
+
+        assert!(!format!("{client:?}").contains("s3cr3t"));
+    }
+
+    #[test]
+    fn report_refused_credentials() {
+        let (url, server) = serve(vec![(401, r#"{ "error": "invalid_client" }"#)]);
+
+        let client = CoverClient::new(&Api {
+            url: Some(url.clone()),
+            auth: Some(Auth::OAuth2 {
+                token_url: format!("{url}/oauth/token"),
+                client_id: "transpiler".to_owned(),
+                client_secret: Some("s3cr3t".to_owned()),
+                scope: None,
+            }),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let message = format!("{:#}", client.project_data("p1").unwrap_err());
+        assert!(message.contains(&format!(
+            "Cover API request to {url}/oauth/token failed : 401 Unauthorized (client credentials refused)"
+        )));
+        assert!(!message.contains("s3cr3t"));
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn read_every_page() {
+        let (url, server) = serve(vec![
+            (
+                200,
+                r#"{ "_embedded": { "projects": [{ "uuid": "p1" }, { "uuid": "p2" }] }, "page": { "totalPages": 2 } }"#,
+            ),
+            (
+                200,
+                r#"{ "_embedded": { "projects": [{ "uuid": "p3" }] }, "page": { "totalPages": 2 } }"#,
+            ),
+            (200, r#"[{ "uuid": "p1", "status": "draft" }]"#),
+        ]);
+
+        let client = client(&url, 0);
+        let uuids = client
+            .projects()
+            .unwrap()
+            .into_iter()
+            .map(|project| project.uuid)
+            .collect::<Vec<_>>();
+        assert_eq!(uuids, ["p1", "p2", "p3"]);
+
+        let projects = client.projects().unwrap();
+        assert_eq!(projects[0].status().as_deref(), Some("draft"));
+
+        let requests = server.join().unwrap();
+        assert!(requests[1].starts_with("GET /project?page=1&size=100 "));
+    }
+
+    #[test]
+    fn read_one_page() {
+        let (url, server) = serve(vec![
+            (
+                200,
+                r#"{ "_embedded": { "projects": [{ "uuid": "p3" }] }, "page": { "totalElements": 3, "totalPages": 2 } }"#,
+            ),
+            (200, r#"[{ "uuid": "p1" }, { "uuid": "p2" }]"#),
+        ]);
+
+        let client = client(&url, 0);
+        let page = client.project_page(1, 2).unwrap();
+        assert_eq!(page.projects.len(), 1);
+        assert_eq!((page.total, page.pages), (Some(3), Some(2)));
+
+        let page = client.project_page(0, 1).unwrap();
+        assert_eq!(page.projects.len(), 2);
+        assert_eq!((page.total, page.pages), (None, None));
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /project?page=1&size=2 "));
+    }
+
+    #[test]
+    fn send_etag() {
+        let (url, server) = serve(vec![(304, ""), (200, r#"{ "structureViews": [] }"#)]);
+
+        let client = client(&url, 0);
+        assert_eq!(
+            client
+                .project_data_if_changed("p1", Some("\"v1\""))
+                .unwrap(),
+            None
+        );
+        let (data, etag) = client.project_data_if_changed("p1", None).unwrap().unwrap();
+        assert_eq!(data.structure_views, Some(vec![]));
+        assert_eq!(etag, None);
+
+        let requests = server.join().unwrap();
+        assert_eq!(header(&requests[0], "if-none-match"), Some("\"v1\""));
+        assert_eq!(header(&requests[1], "if-none-match"), None);
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let temp = tempfile::tempdir().unwrap();
+        let folder = temp.path();
+        let (url, server) = serve(vec![(200, r#"{ "uuid": "p1" }"#), (404, "")]);
+
+        let mut recording = client(&url, 0);
+        recording.fixtures = Some(Fixtures {
+            directory: folder.to_path_buf(),
+            mode: FixtureMode::Record,
+        });
+        assert_eq!(recording.find_by_id(8).unwrap().unwrap().uuid, "p1");
+        assert_eq!(recording.find_by_uuid("p2").unwrap(), None);
+        server.join().unwrap();
+
+        let fixture = fs::read_to_string(folder.join("project_search_findById_id=8.json")).unwrap();
+        assert!(fixture.contains("\"request\": \"GET /project/search/findById?id=8\""));
+
+        // Nothing listens there anymore
+        let mut replaying = client(&url, 0);
+        replaying.fixtures = Some(Fixtures {
+            directory: folder.to_path_buf(),
+            mode: FixtureMode::Replay,
+        });
+        assert_eq!(replaying.find_by_id(8).unwrap().unwrap().uuid, "p1");
+        assert_eq!(replaying.find_by_uuid("p2").unwrap(), None);
+
+        let err = replaying.find_by_name("import").unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("No fixture for `GET /project/search/findByName?name=import`"));
+    }
+
+    fn client(url: &str, retries: u32) -> CoverClient {
+        let mut client = CoverClient::new(&Api {
+            url: Some(url.to_owned()),
+            retries: Some(retries),
+            ..Default::default()
+        })
+        .unwrap();
+        client.retry_delay = Duration::ZERO;
+
+        client
+    }
+
+    #[test]
+    fn retry_unavailable_api() {
+        let (url, server) = serve(vec![
+            (503, ""),
+            (429, ""),
+            (200, r#"{ "uuid": "p1" }"#),
+            (404, ""),
+        ]);
+
+        let retrying = client(&url, 2);
+        assert_eq!(retrying.find_by_id(8).unwrap().unwrap().uuid, "p1");
+        assert_eq!(retrying.find_by_id(1000).unwrap(), None);
+        assert_eq!(server.join().unwrap().len(), 4);
+
+        let (url, server) = serve(vec![(500, ""), (500, "")]);
+        let err = client(&url, 1).project_data("p1").unwrap_err();
+        let err = err.downcast_ref::<ApiError>().unwrap();
+        assert_eq!(err.status, Some(StatusCode::INTERNAL_SERVER_ERROR));
+        assert_eq!(
+            err.to_string(),
+            format!("Cover API request to {url}/documentData/search/findProjectDataByProjectUuid failed : 500 Internal Server Error")
+        );
+        server.join().unwrap();
+
+        let (url, server) = serve(vec![(403, "")]);
+        let err = client(&url, 3).find_by_uuid("p1").unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ApiError>().unwrap().status,
+            Some(StatusCode::FORBIDDEN)
+        );
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn report_unreachable_api() {
+        // Nothing listens on the port once the listener is dropped
+        let url = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("http://{}", listener.local_addr().unwrap())
+        };
+
+        let err = client(&url, 1).find_by_name("import").unwrap_err();
+        let err = err.downcast_ref::<ApiError>().unwrap();
+        assert_eq!(err.status, None);
+        assert!(err.to_string().starts_with(&format!(
+            "Unable to reach Cover API at {url}/project/search/findByName : "
+        )));
+    }
+}
@@ -0,0 +1,136 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::Reply;
+
+/// Whether requests reach the API and their responses are saved, or are
+/// answered from the saved responses.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FixtureMode {
+    Record,
+    Replay,
+}
+
+/// Folder of recorded API responses, one file per request.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Fixtures {
+    pub directory: PathBuf,
+    pub mode: FixtureMode,
+}
+
+/// Recorded response. JSON bodies are kept as is, so that fixtures can be
+/// read and edited.
+#[derive(Deserialize, Serialize)]
+struct Fixture {
+    request: String,
+    status: u16,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<Value>,
+
+    /// Body that is not JSON.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+}
+
+impl Fixtures {
+    pub(super) fn read(&self, path: &str, query: &[(&str, &str)]) -> Result<Reply> {
+        let file = self.directory.join(file_name(path, query));
+        if !file.is_file() {
+            return Err(anyhow!(
+                "No fixture for `{}` : {} does not exist",
+                request(path, query),
+                file.display()
+            ));
+        }
+
+        let fixture: Fixture = serde_json::from_str(
+            &fs::read_to_string(&file)
+                .with_context(|| format!("Unable to read fixture {}", file.display()))?,
+        )
+        .with_context(|| format!("Invalid fixture {}", file.display()))?;
+
+        let body = match (fixture.body, fixture.text) {
+            (Some(body), _) => serde_json::to_vec(&body)?,
+            (None, Some(text)) => text.into_bytes(),
+            (None, None) => vec![],
+        };
+
+        Ok(Reply {
+            status: StatusCode::from_u16(fixture.status)
+                .with_context(|| format!("Invalid fixture {}", file.display()))?,
+            etag: fixture.etag,
+            body,
+        })
+    }
+
+    pub(super) fn write(&self, path: &str, query: &[(&str, &str)], reply: &Reply) -> Result<()> {
+        let (body, text) = match serde_json::from_slice::<Value>(&reply.body) {
+            Ok(body) => (Some(body), None),
+            Err(_) if reply.body.is_empty() => (None, None),
+            Err(_) => (
+                None,
+                Some(String::from_utf8_lossy(&reply.body).into_owned()),
+            ),
+        };
+
+        let fixture = Fixture {
+            request: request(path, query),
+            status: reply.status.as_u16(),
+            etag: reply.etag.clone(),
+            body,
+            text,
+        };
+
+        fs::create_dir_all(&self.directory).with_context(|| {
+            format!(
+                "Unable to create fixture directory {}",
+                self.directory.display()
+            )
+        })?;
+
+        let file = self.directory.join(file_name(path, query));
+        fs::write(&file, serde_json::to_string_pretty(&fixture)? + "\n")
+            .with_context(|| format!("Unable to write fixture {}", file.display()))
+    }
+}
+
+fn request(path: &str, query: &[(&str, &str)]) -> String {
+    let query = query
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    format!("GET {path}?{query}")
+}
+
+/// `/project/search/findById?id=8` is saved as `project_search_findById_id=8.json`.
+fn file_name(path: &str, query: &[(&str, &str)]) -> String {
+    let mut name = path.trim_matches('/').replace('/', "_");
+    for (key, value) in query {
+        name += &format!("_{key}={value}");
+    }
+
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || "-_=.".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    format!("{name}.json")
+}
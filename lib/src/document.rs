@@ -0,0 +1,296 @@
+use anyhow::Result;
+
+use crate::{
+    config::Templates,
+    exporter::{
+        elumatec::{read_tags, tag::Tag, variant::Variant, ElumatecExporter},
+        project_name,
+    },
+    part::Face,
+    ExportOptions, Source,
+};
+
+/// Bar section, from the `:BAR` tag.
+#[derive(Clone, Default)]
+pub(crate) struct Bar {
+    pub width: f32,
+    pub height: f32,
+    /// Vertices as `(x, y, bulge)`.
+    pub polylines: Vec<Vec<(f32, f32, f32)>>,
+    /// Clamp jaws, from `BFT0*`.
+    pub jaws: Option<Jaws>,
+    /// Section envelope, from `BColBox*` : `(from, to, height)` across the width.
+    pub envelope: Vec<(f32, f32, f32)>,
+}
+
+/// Extent of the clamp jaws, around the clamp position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Jaws {
+    pub start: f32,
+    pub length: f32,
+    pub height: f32,
+}
+
+/// `:WORK` placed on the face it is machined from.
+pub(crate) struct Work {
+    pub kind: String,
+    pub number: i32,
+    pub face: Face,
+    pub x: f32,
+    /// Distance from the top (or front) edge of the face.
+    pub y: f32,
+    pub diameter: f32,
+    /// Zero when through.
+    pub depth: f32,
+    /// `WW1`, `WW3` and `WAngle` : size and direction of milled shapes.
+    pub width: f32,
+    pub length: f32,
+    pub angle: f32,
+    pub tool: String,
+    pub comment: String,
+}
+
+/// `:CUT` with the works following it.
+pub(crate) struct Cut {
+    pub number: i32,
+    pub part: i32,
+    pub length: f32,
+    /// Horizontal and vertical angles.
+    pub left: (f32, f32),
+    pub right: (f32, f32),
+    pub bar: Bar,
+    pub works: Vec<Work>,
+}
+
+pub(crate) fn number(tag: &Tag, attr: &str) -> Option<f32> {
+    match tag.get(attr)? {
+        Variant::Int(int) => Some(int as f32),
+        Variant::Float(float) => Some(float),
+        _ => None,
+    }
+}
+
+pub(crate) fn text(tag: &Tag, attr: &str) -> String {
+    match tag.get(attr) {
+        Some(Variant::String(string)) => string,
+        Some(Variant::Null) | None => String::new(),
+        Some(value) => value.to_string(),
+    }
+}
+
+impl Bar {
+    fn from_tag(tag: &Tag) -> Self {
+        let mut polylines = vec![];
+        while let Some(count) = number(tag, &format!("BPolyline{}Count", polylines.len())) {
+            let index = polylines.len();
+            let vertices = (0..count as usize)
+                .filter_map(|vertex| {
+                    let value = text(tag, &format!("BPolyline{index}Vertex{vertex}"));
+                    let coordinates = value
+                        .split_whitespace()
+                        .map(|coordinate| coordinate.parse::<f32>().ok())
+                        .collect::<Option<Vec<_>>>()?;
+
+                    match coordinates[..] {
+                        [x, y] => Some((x, y, 0.0)),
+                        [x, y, bulge, ..] => Some((x, y, bulge)),
+                        _ => None,
+                    }
+                })
+                .collect();
+
+            polylines.push(vertices);
+        }
+
+        // Fixed and moving jaws
+        let jaws = ["S", "M"]
+            .into_iter()
+            .filter_map(|jaw| {
+                Some(Jaws {
+                    start: number(tag, &format!("BFT0TransX{jaw}"))?,
+                    length: number(tag, &format!("BFT0Length{jaw}"))?,
+                    height: number(tag, &format!("BFT0Height{jaw}")).unwrap_or_default(),
+                })
+            })
+            .reduce(|a, b| {
+                let start = a.start.min(b.start);
+                Jaws {
+                    start,
+                    length: (a.start + a.length).max(b.start + b.length) - start,
+                    height: a.height.max(b.height),
+                }
+            });
+
+        // Boxes are given as two corners, on the negative side of the section
+        let mut envelope = vec![];
+        while tag.get(&format!("BColBox{}", envelope.len())).is_some() {
+            let corners = text(tag, &format!("BColBox{}", envelope.len()))
+                .split_whitespace()
+                .filter_map(|corner| corner.parse::<f32>().ok())
+                .map(f32::abs)
+                .collect::<Vec<_>>();
+
+            match corners[..] {
+                [x1, y1, x2, y2] => envelope.push((x1.min(x2), x1.max(x2), y1.max(y2))),
+                _ => break,
+            }
+        }
+
+        Bar {
+            width: number(tag, "BWidth").unwrap_or_default(),
+            height: number(tag, "BHeight").unwrap_or_default(),
+            polylines,
+            jaws,
+            envelope,
+        }
+    }
+
+    /// Size of the views of `face`, unknown sections being drawn as a 50 mm band.
+    pub fn size(&self, face: Face) -> f32 {
+        let size = match face {
+            Face::Front | Face::Back => self.height,
+            Face::Top | Face::Bottom => self.width,
+        };
+
+        if size > 0.0 {
+            size
+        } else {
+            50.0
+        }
+    }
+}
+
+impl Work {
+    fn from_tag(tag: &Tag, bar: &Bar) -> Self {
+        // The working plane is rotated around the bar axis, from the top face
+        let angle = number(tag, "WPAngleX").unwrap_or_default();
+        let face = match (angle.rem_euclid(360.0) / 90.0).round() as i32 % 4 {
+            0 => Face::Top,
+            1 => Face::Back,
+            2 => Face::Bottom,
+            _ => Face::Front,
+        };
+
+        let coordinate = |attr: &str| number(tag, attr).unwrap_or_default();
+        let y = match face {
+            Face::Front | Face::Back => -coordinate("WPTransZ"),
+            Face::Top | Face::Bottom => coordinate("WPTransY") + coordinate("WY1"),
+        };
+
+        Work {
+            kind: text(tag, "WType"),
+            number: coordinate("WNo") as i32,
+            face,
+            x: coordinate("WPTransX") + coordinate("WX1"),
+            y: y.clamp(0.0, bar.size(face)),
+            diameter: coordinate("WDT0D"),
+            depth: coordinate("WDepth"),
+            width: coordinate("WW1"),
+            length: coordinate("WW3"),
+            angle: coordinate("WAngle"),
+            tool: text(tag, "WToolID"),
+            comment: text(tag, "WComment"),
+        }
+    }
+}
+
+impl Work {
+    /// Diameter and length of the removed shape : circular pockets (`C`) are
+    /// `WW1` wide, milled works with a length (`WW3`) are slots, others are
+    /// `WDT0D` holes. Saw cuts and works without size have none.
+    pub fn shape(&self) -> Option<(f32, f32)> {
+        let (diameter, length) = match self.kind.as_str() {
+            "S" => return None,
+            "C" if self.width > 0.0 => (self.width, 0.0),
+            _ if self.width > 0.0 && self.length > 0.0 => (self.width, self.length),
+            _ => (self.diameter, 0.0),
+        };
+
+        (diameter > 0.0).then_some((diameter, length))
+    }
+}
+
+/// Reads the cuts of an Elumatec document, each with the bar it is cut from.
+pub(crate) fn read_cuts(document: &str) -> Result<Vec<Cut>> {
+    let mut bar = Bar::default();
+    let mut cuts: Vec<Cut> = vec![];
+
+    for tag in read_tags(document)? {
+        match tag.name.as_str() {
+            "BAR" => bar = Bar::from_tag(&tag),
+
+            "CUT" => cuts.push(Cut {
+                number: number(&tag, "CNo").unwrap_or_default() as i32,
+                part: number(&tag, "CPartNo").unwrap_or_default() as i32,
+                length: number(&tag, "CLength").unwrap_or_default(),
+                left: (
+                    number(&tag, "CAngleLH").unwrap_or(90.0),
+                    number(&tag, "CAngleLV").unwrap_or(90.0),
+                ),
+                right: (
+                    number(&tag, "CAngleRH").unwrap_or(90.0),
+                    number(&tag, "CAngleRV").unwrap_or(90.0),
+                ),
+                bar: bar.clone(),
+                works: vec![],
+            }),
+
+            "WORK" => {
+                if let Some(cut) = cuts.last_mut() {
+                    let work = Work::from_tag(&tag, &cut.bar);
+                    cut.works.push(work);
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    Ok(cuts)
+}
+
+/// Elumatec documents of `source` : read as is, or generated from a JSON
+/// file without reaching the API.
+pub(crate) fn documents(
+    source: &str,
+    output_path: &Option<String>,
+    options: &ExportOptions,
+    templates: &Templates,
+) -> Result<Vec<(String, String)>> {
+    let project = project_name(&Source::File(source.to_owned()), output_path);
+
+    if source.to_lowercase().ends_with(".json") {
+        let exporter = ElumatecExporter::new(templates);
+        let source = Source::File(source.to_owned());
+        let template = exporter.template(&source, options)?;
+        exporter.documents(&source, template.as_ref(), &project, options)
+    } else {
+        Ok(vec![(project, std::fs::read_to_string(source)?)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn place_works_on_their_face() {
+        let cuts = read_cuts(include_str!("../../examples/elumatec.ncw")).unwrap();
+        let cut = &cuts[0];
+
+        assert_eq!(cut.length, 2003.0);
+        assert_eq!(cut.right.0, 78.47212);
+        assert_eq!(cut.bar.polylines.len(), 2);
+        assert_eq!(cut.bar.polylines[1].len(), 21);
+
+        let face = |number: i32| {
+            cut.works
+                .iter()
+                .find(|work| work.number == number)
+                .map(|work| work.face)
+        };
+        assert_eq!(face(5), Some(Face::Front));
+        assert_eq!(face(6), Some(Face::Back));
+        assert_eq!(face(7), Some(Face::Top));
+    }
+}
@@ -1,27 +1,51 @@
-use anyhow::{anyhow, Result};
-use std::{collections::HashMap, path::Path};
+use anyhow::{anyhow, Context, Result};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
 
 use crate::{
-    util::{find_files_with_extension, move_files_with_extensions},
-    Export, Source,
+    bom::Bom,
+    util::{
+        bom_path, file_stem, find_files_with_extension, is_bom, is_directory, job_path,
+        move_files_with_extensions,
+    },
+    Config, Export, ExportOptions, ExportOutput, Source,
 };
 
-mod elumatec;
+mod csv;
+mod dstv;
+mod dxf;
+pub(crate) mod elumatec;
+mod gcode;
 
 pub struct Exporter {
     exporters: HashMap<String, Box<dyn Export>>,
 }
 
 impl Exporter {
-    pub fn new() -> Self {
+    /// Exporters of every vendor, failing on invalid vendor settings.
+    pub fn new(config: &Config) -> Result<Self> {
         let mut exporters: HashMap<String, Box<dyn Export>> = HashMap::new();
 
         exporters.insert(
             "elumatec".to_owned(),
-            Box::new(elumatec::ElumatecExporter::new()),
+            Box::new(elumatec::ElumatecExporter::new(&config.templates)),
         );
 
-        Exporter { exporters }
+        exporters.insert(
+            "csv".to_owned(),
+            Box::new(csv::CsvExporter::new(&config.csv)?),
+        );
+        exporters.insert("dstv".to_owned(), Box::new(dstv::DstvExporter::new()));
+        exporters.insert(
+            "gcode".to_owned(),
+            Box::new(gcode::GcodeExporter::new(&config.templates)),
+        );
+        exporters.insert("dxf".to_owned(), Box::new(dxf::DxfExporter::new()));
+
+        Ok(Exporter { exporters })
     }
 
     pub fn vendors(&self) -> Vec<String> {
@@ -50,6 +74,7 @@ impl Exporter {
         folder: &str,
         vendor: &str,
         output_path: Option<String>,
+        options: &ExportOptions,
     ) -> Result<()> {
         if let Some(output) = &output_path {
             let path = Path::new(output);
@@ -64,7 +89,18 @@ impl Exporter {
         }
 
         let dest_extension = self.get_file_format(vendor).unwrap();
-        let files = find_files_with_extension(folder, "json")?;
+        // Bills of materials of previous runs sit next to the sources when
+        // there is no output folder
+        let files = find_files_with_extension(folder, "json")?
+            .into_iter()
+            .filter(|file| !is_bom(file));
+
+        // Bills of materials are written straight to their destination,
+        // JSON ones could not be told apart from sources otherwise.
+        let file_options = ExportOptions {
+            bom: None,
+            ..options.clone()
+        };
 
         for file in files {
             let path = std::path::Path::new(&file);
@@ -79,7 +115,28 @@ impl Exporter {
                 path.with_extension(&dest_extension)
                     .to_str()
                     .map(|path| path.to_owned()),
+                &file_options,
             )?;
+
+            if let Some(format) = options.bom {
+                let dest = match &output_path {
+                    Some(output) => {
+                        let relative = path.strip_prefix(folder)?.parent().unwrap();
+                        Path::new(output).join(relative)
+                    }
+                    None => path.parent().unwrap().to_path_buf(),
+                };
+
+                std::fs::create_dir_all(&dest)?;
+                Bom::from_source(&Source::File(file.clone()))?.write(
+                    format,
+                    &bom_path(
+                        dest.to_str().unwrap(),
+                        &file_stem(&file),
+                        format.extension(),
+                    ),
+                )?;
+            }
         }
 
         if let Some(output) = output_path {
@@ -96,7 +153,13 @@ impl Exporter {
         Some(exporter.extension())
     }
 
-    pub fn export(&self, source: Source, vendor: &str, output_path: Option<String>) -> Result<()> {
+    pub fn export(
+        &self,
+        source: Source,
+        vendor: &str,
+        output_path: Option<String>,
+        options: &ExportOptions,
+    ) -> Result<ExportOutput> {
         let record_key = self.get_key(vendor);
         if record_key.is_none() {
             return Err(anyhow!("No exporter implemented for provider `{vendor}`"));
@@ -104,6 +167,158 @@ impl Exporter {
 
         let exporter = self.exporters.get(&record_key.unwrap()).unwrap();
 
-        exporter.export(source, output_path)
+        if let Some(output) = output_path.as_deref().filter(|output| is_directory(output)) {
+            fs::create_dir_all(output)
+                .with_context(|| format!("Unable to create output directory {output}"))?;
+        }
+
+        if let Some(format) = options.bom {
+            let bom = Bom::from_source(&source)?;
+            let project = match &source {
+                Source::Api { project_uuid, .. } => project_uuid.to_owned(),
+                Source::File(path) => file_stem(path),
+            };
+
+            // Printed, it would be mixed up with the jobs
+            let output = output_path.as_ref().ok_or(anyhow!(
+                "Bills of materials are only written to files : set an output"
+            ))?;
+            bom.write(format, &bom_path(output, &project, format.extension()))?;
+        }
+
+        exporter.export(source, output_path, options)
+    }
+}
+
+/// Name jobs are derived from : the output file if any, the source otherwise.
+pub(crate) fn project_name(source: &Source, output_path: &Option<String>) -> String {
+    match (output_path, source) {
+        (Some(output_path), _) if !is_directory(output_path) => file_stem(output_path),
+        (_, Source::Api { project_uuid, .. }) => project_uuid.to_owned(),
+        (_, Source::File(path)) => file_stem(path),
+    }
+}
+
+/// Writes serialized jobs to their own file, or to the standard output.
+/// Several jobs go into the output directory, created if needed, unless the
+/// output has an extension. Returns the files written.
+pub(crate) fn write_jobs(
+    jobs: impl IntoIterator<Item = (String, String)>,
+    output_path: &Option<String>,
+    extension: &str,
+) -> Result<Vec<PathBuf>> {
+    let jobs = jobs.into_iter().collect::<Vec<_>>();
+    let Some(output_path) = output_path else {
+        for (_, serialized) in jobs {
+            println!("{serialized}");
+        }
+        return Ok(vec![]);
+    };
+
+    if is_directory(output_path) || (jobs.len() > 1 && Path::new(output_path).extension().is_none())
+    {
+        fs::create_dir_all(output_path)
+            .with_context(|| format!("Unable to create output directory {output_path}"))?;
+    }
+
+    let files = jobs
+        .iter()
+        .map(|(name, _)| job_path(output_path, name, extension))
+        .collect::<Vec<_>>();
+
+    // Jobs would overwrite each other
+    let mut seen = HashSet::new();
+    if let Some(file) = files.iter().find(|file| !seen.insert(*file)) {
+        return Err(anyhow!(
+            "Several jobs would be written to {} : add `{{view}}`, `{{profile}}` or `{{bar}}` to the name pattern",
+            file.display()
+        ));
+    }
+
+    for (file, (_, serialized)) in files.iter().zip(jobs) {
+        fs::write(file, serialized)
+            .with_context(|| format!("Unable to write {}", file.display()))?;
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bom::BomFormat;
+
+    fn steel() -> String {
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/dstv/steel.json").to_owned()
+    }
+
+    #[test]
+    fn transpile_folder_twice() {
+        let temp = tempfile::tempdir().unwrap();
+        let folder = temp.path();
+        fs::copy(steel(), folder.join("steel.json")).unwrap();
+
+        let options = ExportOptions {
+            bom: Some(BomFormat::Json),
+            ..Default::default()
+        };
+        let exporter = Exporter::new(&Config::default()).unwrap();
+        for _ in 0..2 {
+            exporter
+                .transpile_folder(folder.to_str().unwrap(), "csv", None, &options)
+                .unwrap();
+        }
+
+        let mut files = fs::read_dir(folder)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, ["steel.csv", "steel.json", "steel_bom.json"]);
+    }
+
+    #[test]
+    fn write_bom_to_files_only() {
+        let options = ExportOptions {
+            bom: Some(BomFormat::Csv),
+            ..Default::default()
+        };
+
+        let err = Exporter::new(&Config::default())
+            .unwrap()
+            .export(Source::File(steel()), "csv", None, &options)
+            .unwrap_err();
+        assert!(err.to_string().contains("Bills of materials"));
+    }
+
+    #[test]
+    fn write_jobs_to_directories() {
+        let temp = tempfile::tempdir().unwrap();
+        let folder = temp.path().to_str().unwrap();
+        let jobs = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| (name.to_string(), name.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        let output = Some(format!("{folder}/single/"));
+        let files = write_jobs(jobs(&["job"]), &output, "ncw").unwrap();
+        assert_eq!(files, [Path::new(folder).join("single/job.ncw")]);
+
+        let output = Some(format!("{folder}/split"));
+        let files = write_jobs(jobs(&["F1", "F2"]), &output, "ncw").unwrap();
+        assert_eq!(
+            files,
+            [
+                Path::new(folder).join("split/F1.ncw"),
+                Path::new(folder).join("split/F2.ncw")
+            ]
+        );
+
+        let output = Some(format!("{folder}/same"));
+        let err = write_jobs(jobs(&["job", "job"]), &output, "ncw").unwrap_err();
+        assert!(err.to_string().contains("Several jobs"));
+        assert!(!Path::new(folder).join("same/job.ncw").exists());
     }
 }
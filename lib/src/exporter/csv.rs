@@ -0,0 +1,272 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+
+use super::{project_name, write_jobs};
+use crate::{config::Csv, part::Part, Export, ExportOptions, ExportOutput, Source};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Column {
+    Profile,
+    Length,
+    AngleLH,
+    AngleLV,
+    AngleRH,
+    AngleRV,
+    Quantity,
+    PartNo,
+    Comment,
+    View,
+    Bar,
+}
+
+impl Column {
+    const ALL: [Column; 11] = [
+        Column::Profile,
+        Column::Length,
+        Column::AngleLH,
+        Column::AngleLV,
+        Column::AngleRH,
+        Column::AngleRV,
+        Column::Quantity,
+        Column::PartNo,
+        Column::Comment,
+        Column::View,
+        Column::Bar,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Profile => "profile",
+            Self::Length => "length",
+            Self::AngleLH => "angle_lh",
+            Self::AngleLV => "angle_lv",
+            Self::AngleRH => "angle_rh",
+            Self::AngleRV => "angle_rv",
+            Self::Quantity => "quantity",
+            Self::PartNo => "part_no",
+            Self::Comment => "comment",
+            Self::View => "view",
+            Self::Bar => "bar",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|column| column.name() == name.trim().to_lowercase())
+    }
+}
+
+/// Cut list for manual saws, one row per part.
+#[derive(Clone)]
+pub struct CsvExporter {
+    columns: Vec<Column>,
+    separator: char,
+    decimal_separator: char,
+}
+
+impl Default for CsvExporter {
+    fn default() -> Self {
+        CsvExporter {
+            columns: Column::ALL[..9].to_vec(),
+            separator: ',',
+            decimal_separator: '.',
+        }
+    }
+}
+
+impl CsvExporter {
+    /// Reads the layout from the `[csv]` settings, e.g. `;` and `,` for
+    /// French spreadsheets.
+    pub fn new(settings: &Csv) -> Result<Self> {
+        Self::from_settings(settings).context("Invalid CSV settings")
+    }
+
+    fn from_settings(settings: &Csv) -> Result<Self> {
+        let mut exporter = Self::default();
+
+        if let Some(columns) = &settings.columns {
+            exporter.columns = columns
+                .iter()
+                .map(|name| Column::parse(name).ok_or(anyhow!("Unknown CSV column `{name}`")))
+                .collect::<Result<_>>()?;
+        }
+
+        if let Some(separator) = &settings.separator {
+            exporter.separator = single_char(separator, "csv.separator")?;
+        }
+
+        if let Some(decimal_separator) = &settings.decimal {
+            exporter.decimal_separator = single_char(decimal_separator, "csv.decimal")?;
+        }
+
+        if exporter.separator == exporter.decimal_separator {
+            return Err(anyhow!(
+                "csv.separator and csv.decimal must be different characters"
+            ));
+        }
+
+        Ok(exporter)
+    }
+
+    fn number(&self, value: f32) -> String {
+        value
+            .to_string()
+            .replace('.', &self.decimal_separator.to_string())
+    }
+
+    fn field(&self, field: String) -> String {
+        if field.contains([self.separator, '"', '\n']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field
+        }
+    }
+
+    fn row(&self, part: &Part) -> String {
+        let fields = self
+            .columns
+            .iter()
+            .map(|column| {
+                self.field(match column {
+                    Column::Profile => part.refs.profile.clone(),
+                    Column::Length => self.number(part.length),
+                    Column::AngleLH => self.number(part.left.h),
+                    Column::AngleLV => self.number(part.left.v),
+                    Column::AngleRH => self.number(part.right.h),
+                    Column::AngleRV => self.number(part.right.v),
+                    Column::Quantity => part.quantity.to_string(),
+                    Column::PartNo => part.number.to_string(),
+                    Column::Comment => part.comment.clone(),
+                    Column::View => part.refs.view.clone(),
+                    Column::Bar => part.refs.bar.clone(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        fields.join(&self.separator.to_string())
+    }
+
+    fn serialize(&self, parts: &[Part]) -> String {
+        let header = self
+            .columns
+            .iter()
+            .map(|column| column.name())
+            .collect::<Vec<_>>();
+
+        let mut serialized = header.join(&self.separator.to_string()) + "\n";
+        for part in parts {
+            serialized += &self.row(part);
+            serialized += "\n";
+        }
+
+        serialized
+    }
+}
+
+fn single_char(value: &str, variable: &str) -> Result<char> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(anyhow!("{variable} must be a single character")),
+    }
+}
+
+impl Export for CsvExporter {
+    fn extension(&self) -> String {
+        "csv".to_owned()
+    }
+
+    fn export(
+        &self,
+        source: Source,
+        output_path: Option<String>,
+        options: &ExportOptions,
+    ) -> Result<ExportOutput> {
+        if let Source::File(file) = &source {
+            if !Path::new(file).is_file() {
+                return Err(anyhow!("Source must be a file"));
+            }
+        }
+
+        let parts = Part::from_source(&source)?;
+        let project = project_name(&source, &output_path);
+
+        let jobs = Part::group(parts, options.split)?
+            .into_iter()
+            .map(|(refs, parts)| (options.file_name(&project, &refs), self.serialize(&parts)));
+
+        Ok(ExportOutput::new(
+            write_jobs(jobs, &output_path, &self.extension())?,
+            None,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{part::Extremity, PartRefs};
+
+    fn part() -> Part {
+        Part {
+            refs: PartRefs {
+                view: "F1".to_owned(),
+                profile: "L100".to_owned(),
+                bar: "1".to_owned(),
+            },
+            length: 1250.5,
+            left: Extremity {
+                h: 45.0,
+                v: 90.0,
+                z: None,
+            },
+            right: Extremity {
+                h: 67.5,
+                v: 90.0,
+                z: None,
+            },
+            quantity: 2,
+            number: 1,
+            comment: "F1; left".to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn serialize_default_columns() {
+        let csv = CsvExporter::default().serialize(&[part()]);
+        assert_eq!(
+            csv,
+            "profile,length,angle_lh,angle_lv,angle_rh,angle_rv,quantity,part_no,comment\n\
+             L100,1250.5,45,90,67.5,90,2,1,F1; left\n"
+        );
+    }
+
+    #[test]
+    fn serialize_with_decimal_comma() {
+        let exporter = CsvExporter {
+            columns: vec![Column::Profile, Column::Length, Column::Comment],
+            separator: ';',
+            decimal_separator: ',',
+        };
+
+        let csv = exporter.serialize(&[part()]);
+        assert_eq!(csv, "profile;length;comment\nL100;1250,5;\"F1; left\"\n");
+    }
+    #[test]
+    fn refuse_invalid_settings() {
+        let settings = |separator: &str| Csv {
+            separator: Some(separator.to_owned()),
+            decimal: Some(",".to_owned()),
+            ..Default::default()
+        };
+
+        assert_eq!(CsvExporter::new(&settings(";")).unwrap().separator, ';');
+        let Err(err) = CsvExporter::new(&settings(";;")) else {
+            panic!("`;;` is not a separator");
+        };
+        assert!(format!("{err:#}").starts_with("Invalid CSV settings"));
+    }
+}
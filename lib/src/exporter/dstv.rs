@@ -0,0 +1,209 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+use super::{project_name, write_jobs};
+use crate::{
+    part::{Face, MachiningKind, Part},
+    Export, ExportOptions, ExportOutput, Source,
+};
+
+/// DSTV NC1 files for steel profile machines, one per part.
+#[derive(Clone, Default)]
+pub struct DstvExporter;
+
+impl DstvExporter {
+    pub fn new() -> Self {
+        DstvExporter
+    }
+
+    fn serialize(part: &Part, order: &str) -> String {
+        let section = &part.section;
+        let text = |value: &str| {
+            if value.is_empty() {
+                "-".to_owned()
+            } else {
+                value.to_owned()
+            }
+        };
+        let profile = if section.name.is_empty() {
+            &part.refs.profile
+        } else {
+            &section.name
+        };
+        let code = if section.code.is_empty() {
+            "SO"
+        } else {
+            &section.code
+        };
+
+        let header = vec![
+            text(order),
+            text(&part.refs.view),
+            "1".to_owned(),
+            format!("P{}", part.number),
+            text(&section.material),
+            part.quantity.to_string(),
+            text(profile),
+            code.to_owned(),
+            format!("{:.2}", part.length),
+            format!("{:.2}", section.height),
+            format!("{:.2}", section.width),
+            format!("{:.2}", section.flange_thickness),
+            format!("{:.2}", section.web_thickness),
+            format!("{:.2}", section.radius),
+            format!("{:.3}", section.weight),
+            format!("{:.3}", 0.0),
+            // DSTV angles are deviations from a square cut
+            format!("{:.3}", 90.0 - part.left.h),
+            format!("{:.3}", 90.0 - part.right.h),
+            format!("{:.3}", 90.0 - part.left.v),
+            format!("{:.3}", 90.0 - part.right.v),
+            text(&part.comment),
+            "-".to_owned(),
+            "-".to_owned(),
+            "-".to_owned(),
+        ];
+
+        let holes = part
+            .machinings
+            .iter()
+            .filter(|machining| machining.kind != MachiningKind::Contour)
+            .map(|machining| {
+                let face = match machining.face {
+                    Face::Front => 'v',
+                    Face::Top => 'o',
+                    Face::Bottom => 'u',
+                    Face::Back => 'h',
+                };
+
+                let mut hole = format!(
+                    "{face} {:>10.2} {:>9.2} {:>7.2}",
+                    machining.x, machining.y, machining.diameter
+                );
+
+                if machining.kind == MachiningKind::Slot {
+                    hole += &format!(
+                        " {:>7.2}l {:>7.2} {:>7.2} {:>7.2}",
+                        machining.depth.unwrap_or_default(),
+                        machining.length,
+                        0.0,
+                        0.0
+                    );
+                } else if let Some(depth) = machining.depth {
+                    hole += &format!(" {depth:>7.2}");
+                }
+
+                hole
+            })
+            .collect::<Vec<_>>();
+
+        let mut serialized = format!("ST\n** {} part {}\n", text(profile), part.number);
+        serialized += &indent(&header);
+
+        if !holes.is_empty() {
+            serialized += "BO\n";
+            serialized += &indent(&holes);
+        }
+
+        serialized += "EN\n";
+
+        serialized
+    }
+}
+
+/// Block data is indented by two spaces, under the block name.
+fn indent(lines: &[String]) -> String {
+    lines.iter().map(|line| format!("  {line}\n")).collect()
+}
+
+impl Export for DstvExporter {
+    fn extension(&self) -> String {
+        "nc1".to_owned()
+    }
+
+    fn export(
+        &self,
+        source: Source,
+        output_path: Option<String>,
+        options: &ExportOptions,
+    ) -> Result<ExportOutput> {
+        if let Source::File(file) = &source {
+            if !Path::new(file).is_file() {
+                return Err(anyhow!("Source must be a file"));
+            }
+        }
+
+        let parts = Part::from_source(&source)?;
+        let project = project_name(&source, &output_path);
+
+        let jobs = parts
+            .iter()
+            .map(|part| {
+                let name = options.file_name(&project, &options.split.key(&part.refs)?);
+                Ok((
+                    format!("{name}_{}", part.number),
+                    Self::serialize(part, &project),
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(ExportOutput::new(
+            write_jobs(jobs, &output_path, &self.extension())?,
+            None,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part::{Machining, Section};
+
+    #[test]
+    fn serialize_blind_hole() {
+        let part = Part {
+            length: 1000.0,
+            left: crate::part::Extremity {
+                h: 90.0,
+                v: 90.0,
+                z: None,
+            },
+            right: crate::part::Extremity {
+                h: 45.0,
+                v: 90.0,
+                z: None,
+            },
+            quantity: 1,
+            number: 1,
+            section: Section {
+                name: "IPE200".to_owned(),
+                code: "I".to_owned(),
+                ..Default::default()
+            },
+            machinings: vec![Machining {
+                face: Face::Top,
+                x: 50.0,
+                y: 25.0,
+                diameter: 18.0,
+                depth: Some(5.0),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let serialized = DstvExporter::serialize(&part, "order");
+        let lines = serialized.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[0], "ST");
+        assert_eq!(lines[8], "  IPE200");
+        assert_eq!(lines[9], "  I");
+        assert_eq!(lines[19], "  45.000");
+        assert_eq!(lines[lines.len() - 3], "BO");
+        assert_eq!(
+            lines[lines.len() - 2],
+            "  o      50.00     25.00   18.00    5.00"
+        );
+        assert_eq!(lines[lines.len() - 1], "EN");
+    }
+}
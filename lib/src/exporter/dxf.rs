@@ -0,0 +1,374 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+use super::{project_name, write_jobs};
+use crate::{
+    part::{Face, MachiningKind, Part},
+    Export, ExportOptions, ExportOutput, Source,
+};
+
+const CUTS: &str = "CUTS";
+const MACHININGS: &str = "MACHININGS";
+const DIMENSIONS: &str = "DIMENSIONS";
+
+/// Gap between the views of a part, in millimeters.
+const VIEW_GAP: f32 = 80.0;
+
+/// Height of dimension texts, in millimeters.
+const TEXT_HEIGHT: f32 = 10.0;
+
+/// Entities of an ASCII DXF (R12) drawing.
+#[derive(Default)]
+struct Drawing {
+    entities: Vec<(i32, String)>,
+}
+
+impl Drawing {
+    fn entity(&mut self, kind: &str, layer: &str) {
+        self.entities.push((0, kind.to_owned()));
+        self.entities.push((8, layer.to_owned()));
+    }
+
+    fn point(&mut self, code: i32, (x, y): (f32, f32)) {
+        self.entities.push((code, format!("{x:.3}")));
+        self.entities.push((code + 10, format!("{y:.3}")));
+    }
+
+    fn value(&mut self, code: i32, value: f32) {
+        self.entities.push((code, format!("{value:.3}")));
+    }
+
+    fn line(&mut self, layer: &str, start: (f32, f32), end: (f32, f32)) {
+        self.entity("LINE", layer);
+        self.point(10, start);
+        self.point(11, end);
+    }
+
+    fn polygon(&mut self, layer: &str, points: &[(f32, f32)]) {
+        for (index, start) in points.iter().enumerate() {
+            let end = points[(index + 1) % points.len()];
+            self.line(layer, *start, end);
+        }
+    }
+
+    fn circle(&mut self, layer: &str, center: (f32, f32), radius: f32) {
+        self.entity("CIRCLE", layer);
+        self.point(10, center);
+        self.value(40, radius);
+    }
+
+    /// Counterclockwise arc, angles in degrees.
+    fn arc(&mut self, layer: &str, center: (f32, f32), radius: f32, start: f32, end: f32) {
+        self.entity("ARC", layer);
+        self.point(10, center);
+        self.value(40, radius);
+        self.value(50, start);
+        self.value(51, end);
+    }
+
+    fn text(&mut self, layer: &str, position: (f32, f32), text: &str) {
+        self.entity("TEXT", layer);
+        self.point(10, position);
+        self.value(40, TEXT_HEIGHT);
+        self.entities.push((1, text.to_owned()));
+    }
+
+    fn serialize(&self) -> String {
+        let mut pairs = vec![
+            (0, "SECTION".to_owned()),
+            (2, "TABLES".to_owned()),
+            (0, "TABLE".to_owned()),
+            (2, "LAYER".to_owned()),
+            (70, "3".to_owned()),
+        ];
+
+        for (layer, color) in [(CUTS, 7), (MACHININGS, 1), (DIMENSIONS, 3)] {
+            pairs.extend([
+                (0, "LAYER".to_owned()),
+                (2, layer.to_owned()),
+                (70, "0".to_owned()),
+                (62, color.to_string()),
+                (6, "CONTINUOUS".to_owned()),
+            ]);
+        }
+
+        pairs.extend([
+            (0, "ENDTAB".to_owned()),
+            (0, "ENDSEC".to_owned()),
+            (0, "SECTION".to_owned()),
+            (2, "ENTITIES".to_owned()),
+        ]);
+        pairs.extend(self.entities.iter().cloned());
+        pairs.extend([(0, "ENDSEC".to_owned()), (0, "EOF".to_owned())]);
+
+        pairs
+            .into_iter()
+            .map(|(code, value)| format!("{code:>3}\n{value}\n"))
+            .collect()
+    }
+}
+
+/// Elevation drawings of cut parts, one per part.
+#[derive(Clone, Default)]
+pub struct DxfExporter;
+
+impl DxfExporter {
+    pub fn new() -> Self {
+        DxfExporter
+    }
+
+    /// Draws the front view with the horizontal cut angles and the top view
+    /// with the vertical ones. Bottom and back views are only drawn when
+    /// they are machined.
+    fn draw(part: &Part) -> Drawing {
+        let mut drawing = Drawing::default();
+
+        let height = part.section.height;
+        let width = part.section.width;
+
+        let mut views = vec![
+            (Face::Front, height, part.left.h, part.right.h),
+            (Face::Top, width, part.left.v, part.right.v),
+        ];
+        for face in [Face::Bottom, Face::Back] {
+            if part
+                .machinings
+                .iter()
+                .any(|machining| machining.face == face)
+            {
+                let size = if face == Face::Bottom { width } else { height };
+                views.push((face, size, part.left.v, part.right.v));
+            }
+        }
+
+        let mut origin_y = 0.0;
+        for (face, size, left, right) in views {
+            // Unknown sections are drawn as a 50 mm band
+            let size = if size > 0.0 { size } else { 50.0 };
+            origin_y -= size;
+
+            let offset = |angle: f32| size / angle.to_radians().tan();
+            let (left_offset, right_offset) = (offset(left), offset(right));
+            drawing.polygon(
+                CUTS,
+                &[
+                    (0.0, origin_y),
+                    (part.length, origin_y),
+                    (part.length - right_offset, origin_y + size),
+                    (left_offset, origin_y + size),
+                ],
+            );
+
+            Self::draw_dimensions(&mut drawing, part, origin_y, size, face, (left, right));
+
+            for machining in part
+                .machinings
+                .iter()
+                .filter(|machining| machining.face == face)
+            {
+                let (x, y) = (machining.x, origin_y + machining.y);
+                let radius = machining.diameter / 2.0;
+
+                match machining.kind {
+                    MachiningKind::Drilling => drawing.circle(MACHININGS, (x, y), radius),
+
+                    MachiningKind::Slot => {
+                        let end = x + machining.length;
+                        drawing.arc(MACHININGS, (x, y), radius, 90.0, 270.0);
+                        drawing.arc(MACHININGS, (end, y), radius, 270.0, 90.0);
+                        drawing.line(MACHININGS, (x, y - radius), (end, y - radius));
+                        drawing.line(MACHININGS, (x, y + radius), (end, y + radius));
+                    }
+
+                    MachiningKind::Contour => {
+                        let points = machining
+                            .points
+                            .iter()
+                            .map(|(point_x, point_y)| (x + point_x, y + point_y))
+                            .collect::<Vec<_>>();
+
+                        if !points.is_empty() {
+                            drawing.polygon(MACHININGS, &points);
+                        }
+                    }
+                }
+            }
+
+            origin_y -= VIEW_GAP;
+        }
+
+        drawing
+    }
+
+    fn draw_dimensions(
+        drawing: &mut Drawing,
+        part: &Part,
+        origin_y: f32,
+        size: f32,
+        face: Face,
+        (left, right): (f32, f32),
+    ) {
+        let dimension_y = origin_y - TEXT_HEIGHT * 2.0;
+        drawing.line(DIMENSIONS, (0.0, origin_y), (0.0, dimension_y));
+        drawing.line(
+            DIMENSIONS,
+            (part.length, origin_y),
+            (part.length, dimension_y),
+        );
+        drawing.line(DIMENSIONS, (0.0, dimension_y), (part.length, dimension_y));
+        drawing.text(
+            DIMENSIONS,
+            (part.length / 2.0, dimension_y + TEXT_HEIGHT / 2.0),
+            &format!("{}", part.length),
+        );
+
+        let angle_y = origin_y + size + TEXT_HEIGHT / 2.0;
+        drawing.text(DIMENSIONS, (0.0, angle_y), &format!("{left}%%d"));
+        drawing.text(
+            DIMENSIONS,
+            (part.length - TEXT_HEIGHT * 4.0, angle_y),
+            &format!("{right}%%d"),
+        );
+
+        let view = format!("{face:?}").to_uppercase();
+        drawing.text(
+            DIMENSIONS,
+            (-TEXT_HEIGHT * 10.0, origin_y + size / 2.0),
+            &format!("{view} P{}", part.number),
+        );
+    }
+}
+
+impl Export for DxfExporter {
+    fn extension(&self) -> String {
+        "dxf".to_owned()
+    }
+
+    fn export(
+        &self,
+        source: Source,
+        output_path: Option<String>,
+        options: &ExportOptions,
+    ) -> Result<ExportOutput> {
+        if let Source::File(file) = &source {
+            if !Path::new(file).is_file() {
+                return Err(anyhow!("Source must be a file"));
+            }
+        }
+
+        let parts = Part::from_source(&source)?;
+        let project = project_name(&source, &output_path);
+
+        let jobs = parts
+            .iter()
+            .map(|part| {
+                let name = options.file_name(&project, &options.split.key(&part.refs)?);
+                Ok((
+                    format!("{name}_{}", part.number),
+                    Self::draw(part).serialize(),
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(ExportOutput::new(
+            write_jobs(jobs, &output_path, &self.extension())?,
+            None,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part::{Extremity, Machining, Section};
+
+    fn lines(drawing: &Drawing, layer: &str) -> Vec<Vec<String>> {
+        let mut entities: Vec<Vec<String>> = vec![];
+        for (code, value) in &drawing.entities {
+            if *code == 0 {
+                entities.push(vec![value.clone()]);
+            } else {
+                entities.last_mut().unwrap().push(value.clone());
+            }
+        }
+
+        entities
+            .into_iter()
+            .filter(|entity| entity[0] == "LINE" && entity[1] == layer)
+            .collect()
+    }
+
+    #[test]
+    fn draw_mitred_ends() {
+        let part = Part {
+            length: 1000.0,
+            left: Extremity {
+                h: 45.0,
+                v: 90.0,
+                z: None,
+            },
+            right: Extremity {
+                h: 90.0,
+                v: 90.0,
+                z: None,
+            },
+            section: Section {
+                height: 100.0,
+                width: 40.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let drawing = DxfExporter::draw(&part);
+        let cuts = lines(&drawing, CUTS);
+
+        // Front view, then top view
+        assert_eq!(cuts.len(), 8);
+        assert_eq!(cuts[2][2..], ["1000.000", "0.000", "100.000", "0.000"]);
+        assert_eq!(cuts[3][2..], ["100.000", "0.000", "0.000", "-100.000"]);
+        assert_eq!(cuts[4][2..], ["0.000", "-220.000", "1000.000", "-220.000"]);
+    }
+
+    #[test]
+    fn draw_machinings_on_their_face() {
+        let part = Part {
+            length: 500.0,
+            left: Extremity {
+                h: 90.0,
+                v: 90.0,
+                z: None,
+            },
+            right: Extremity {
+                h: 90.0,
+                v: 90.0,
+                z: None,
+            },
+            machinings: vec![
+                Machining {
+                    face: Face::Back,
+                    x: 100.0,
+                    y: 20.0,
+                    diameter: 8.0,
+                    ..Default::default()
+                },
+                Machining {
+                    kind: MachiningKind::Slot,
+                    x: 200.0,
+                    y: 20.0,
+                    diameter: 10.0,
+                    length: 30.0,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let serialized = DxfExporter::draw(&part).serialize();
+        assert!(serialized.contains("  0\nCIRCLE\n  8\nMACHININGS\n 10\n100.000\n 20\n-290.000\n"));
+        assert_eq!(serialized.matches("ARC\n").count(), 2);
+        assert!(serialized.contains("BACK P0"));
+        assert!(serialized.ends_with("  0\nEOF\n"));
+    }
+}
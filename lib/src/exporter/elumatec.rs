@@ -1,31 +1,35 @@
-use std::{
-    fs::{self, File},
-    io::Read,
-    path::Path,
-};
+use std::{fmt::Display, path::Path};
 
 use self::{tag::Tag, variant::Variant};
-use crate::{Export, Source};
+use super::{project_name, write_jobs};
+use crate::{
+    clamp, config::Templates, cover::ProjectData, part::Part, template::Template, Export,
+    ExportOptions, ExportOutput, Source,
+};
 use anyhow::{anyhow, Result};
-use reqwest::{blocking::Client, Url};
+use serde_json::{json, Value};
 
-mod tag;
-mod variant;
+pub(crate) mod tag;
+pub(crate) mod variant;
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct ElumatecExporter {
     tags: Vec<Tag>,
+    templates: Templates,
+    /// Rendered for each job, instead of starting from `tags`.
+    template: Option<Template>,
 }
 
-impl ElumatecExporter {
-    pub fn new() -> Self {
-        match Self::from_template() {
-            Ok(exporter) => exporter,
+/// `OCreator` of the jobs.
+const CREATOR: &str = "Elucad";
 
-            Err(err) => {
-                eprintln!("Unable to read template file : \n{}", err.to_string());
-                Self::default()
-            }
+impl ElumatecExporter {
+    /// Jobs start from the `elumatec` template, or from the one of the
+    /// machine, found in `templates` when exporting.
+    pub fn new(templates: &Templates) -> Self {
+        Self {
+            templates: templates.clone(),
+            ..Default::default()
         }
     }
 
@@ -52,108 +56,47 @@ impl ElumatecExporter {
         }
     }
 
-    fn update_cuts(&mut self, project_uuid: &str) -> Result<()> {
-        let base_url = std::env::var("BASE_URL")?;
-        let url =
-            Url::parse(&base_url)?.join("/documentData/search/findProjectDataByProjectUuid")?;
-
-        let res = Client::new()
-            .get(url)
-            .query(&[("projectUuid", project_uuid)])
-            .send()?;
-        let data = res.json::<serde_json::Value>()?;
-
-        let structure_views = &data["structureViews"];
-        if structure_views.is_null() {
-            return Err(anyhow!("Unable to update cuts, `structureViews` is null"));
-        }
-
-        let structure_views = structure_views.as_array().unwrap();
-        if structure_views.is_empty() {
-            return Err(anyhow!("Unable to update cuts, no profile found"));
-        }
+    fn cut_tag(part: &Part) -> Tag {
+        let mut cut = Tag::new("CUT");
+        cut.set("CLength", Variant::Float(part.length));
+        cut.set("CPartNo", Variant::Int(part.number as i32));
+        cut.set("CComNo", Variant::String(part.comment.clone()));
 
-        let structure_view = &structure_views[0];
-        let profiles = &structure_view["nomenclature"]["profiles"];
+        cut.set("CAngleLH", Variant::Float(part.left.h));
+        cut.set("CAngleLV", Variant::Float(part.left.v));
+        cut.set("CAngleRH", Variant::Float(part.right.h));
+        cut.set("CAngleRV", Variant::Float(part.right.v));
 
-        if profiles.is_null() {
-            return Err(anyhow!("Unable to update cuts, `profiles` is null"));
+        if let (Some(left), Some(right)) = (part.left.z, part.right.z) {
+            cut.set("CutLossL", Variant::Float(left));
+            cut.set("CutLossR", Variant::Float(part.length + right));
         }
 
-        let mut cut_tags = vec![];
-
-        let profiles = profiles.as_array().unwrap();
-        for profile in profiles {
-            let length = profile["length"].as_f64().unwrap() as f32;
-
-            let left = profile["extremity1"]["cuts"].as_array().unwrap();
-            let right = profile["extremity2"]["cuts"].as_array().unwrap();
-
-            for i in 0..left.len() {
-                let mut cut = Tag::new("CUT");
-                cut.set("CLength", Variant::Float(length as f32));
-
-                let cut_index = cut_tags.len() + 1;
-                cut.set("CNo", Variant::Int(cut_index as i32));
-
-                let cut_object = &left[i];
-                cut.set(
-                    "CAngleLH",
-                    Variant::Float(cut_object["h"].as_f64().unwrap() as f32),
-                );
-                cut.set(
-                    "CAngleLV",
-                    Variant::Float(cut_object["v"].as_f64().unwrap() as f32),
-                );
-                cut.set(
-                    "CutLossL",
-                    Variant::Float(cut_object["z"].as_f64().unwrap() as f32),
-                );
-
-                let cut_object = &right[i];
-                cut.set(
-                    "CAngleRH",
-                    Variant::Float(cut_object["h"].as_f64().unwrap() as f32),
-                );
-                cut.set(
-                    "CAngleRV",
-                    Variant::Float(cut_object["v"].as_f64().unwrap() as f32),
-                );
-                cut.set(
-                    "CutLossR",
-                    Variant::Float(length + cut_object["z"].as_f64().unwrap() as f32),
-                );
+        cut.set("CRotation", Variant::Float(0.0));
+        cut.set("CSawRotation", Variant::Float(0.0));
 
-                cut_tags.push(cut);
-            }
-        }
+        cut
+    }
 
+    /// Appends cuts to the job, numbering them and setting the job cut count.
+    fn update_cuts(&mut self, mut cut_tags: Vec<Tag>) {
         let cut_count = cut_tags.len() as i32;
-        for tag in &mut cut_tags {
+        for (cut_index, tag) in cut_tags.iter_mut().enumerate() {
+            tag.set("CNo", Variant::Int(cut_index as i32 + 1));
             tag.set("CCount", Variant::Int(cut_count));
-            self.tags.push(tag.clone());
         }
 
-        Ok(())
+        self.tags.extend(cut_tags);
     }
 
-    fn update_macros(&mut self, project_uuid: &str) -> Result<()> {
-        let base_url = std::env::var("BASE_URL")?;
-        let url =
-            Url::parse(&base_url)?.join("/documentData/search/findProjectDataByProjectUuid")?;
-
-        let res = Client::new()
-            .get(url)
-            .query(&[("projectUuid", project_uuid)])
-            .send()?;
-        let data = res.json::<serde_json::Value>()?;
-
-        let structure_views = &data["structureViews"];
-        if structure_views.is_null() {
-            return Err(anyhow!("Unable to update macros, `structureViews` is null"));
-        }
+    /// `Var*` macro values of the job, from the parameters of the first
+    /// project operation setting any : none without machinings.
+    fn macros(data: &ProjectData) -> Result<Vec<(String, Variant)>> {
+        let structure_views = data
+            .structure_views
+            .as_ref()
+            .ok_or(anyhow!("Unable to update macros, `structureViews` is null"))?;
 
-        let structure_views = structure_views.as_array().unwrap();
         for structure_view in structure_views {
             let nomenclature = &structure_view["nomenclature"];
             if nomenclature.is_null() {
@@ -192,186 +135,214 @@ impl ElumatecExporter {
                         }
 
                         let params = params.as_object().unwrap();
-                        let mut var_set = false;
+                        let mut vars = vec![];
 
                         for (key, value) in params {
                             if key.as_bytes()[0] != b'v' {
                                 continue;
                             }
 
-                            var_set = true;
-
-                            let var_index = (&key[1..]).parse::<u16>()?;
-                            self.set_attribute(
-                                "JOB",
-                                &format!("Var{}", var_index - 1),
+                            let var_index = key[1..].parse::<u16>()?;
+                            vars.push((
+                                format!("Var{}", var_index - 1),
                                 Variant::from(value.as_str().unwrap()),
-                            );
+                            ));
                         }
 
-                        if var_set {
-                            return Ok(());
+                        if !vars.is_empty() {
+                            return Ok(vars);
                         }
                     }
                 }
             }
         }
 
-        Err(anyhow!("Unable to update macros : no machinings set"))
+        Ok(vec![])
     }
 
-    fn update_from_api(&mut self, project_uuid: &str) -> Result<()> {
-        if let Err(err) = self.update_macros(project_uuid) {
-            eprintln!("{}", err.to_string());
-        }
-
-        if let Err(err) = self.update_cuts(project_uuid) {
-            eprintln!("{}", err.to_string());
-        }
-
-        // other substitutions that should be done
-
-        Ok(())
+    /// Template of API projects : the one of the machine, or `elumatec`.
+    fn template_name(options: &ExportOptions) -> String {
+        options
+            .machine
+            .as_ref()
+            .map_or("elumatec", |machine| machine.template.as_str())
+            .to_owned()
     }
 
-    fn update_from_file(&mut self, file: &str) -> Result<()> {
-        self.set_attribute("OPTIONS", "OScale", Variant::Int(1));
-        self.set_attribute("OPTIONS", "OCreator", Variant::String("Elucad".to_owned()));
-
-        let mut file = File::open(file)?;
-
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-
-        let json: serde_json::Value = serde_json::from_str(&contents)?;
+    /// Values the template of a job is rendered with.
+    fn context(project: &str, job: &str, parts: &[Part], options: &ExportOptions) -> Value {
+        json!({
+            "project": project,
+            "job": job,
+            "creator": CREATOR,
+            "machine": options.machine,
+            "parts": parts,
+        })
+    }
 
-        let mut cut_tags = vec![];
+    /// Groups parts into jobs according to `options.split`, each job being
+    /// named after `options.name_pattern`.
+    fn jobs(
+        &self,
+        parts: Vec<Part>,
+        project: &str,
+        options: &ExportOptions,
+    ) -> Result<Vec<(String, Self)>> {
+        Part::group(parts, options.split)?
+            .into_iter()
+            .map(|(refs, parts)| {
+                let name = options.file_name(project, &refs);
+
+                let mut job = self.clone();
+                if let Some(template) = &self.template {
+                    let context = Self::context(project, &name, &parts, options);
+                    job.tags = template.tags(&context, &["OPTIONS", "JOB"])?;
+                }
 
-        let articles = json["articles"].as_array().unwrap();
-        for article in articles {
-            match article["type"].as_str().unwrap() {
-                "profile" => {
-                    let length = article["length"]["value"].as_f64().unwrap();
+                // Cuts written by the template are kept as they are
+                if !job.has_tag("CUT") {
+                    job.update_cuts(parts.iter().map(Self::cut_tag).collect());
+                }
 
-                    let cuts = article["cuts"].as_array().unwrap();
-                    let left = &cuts[0].as_array().unwrap();
-                    let right = &cuts[1].as_array().unwrap();
+                Ok((name, job))
+            })
+            .collect()
+    }
 
-                    for i in 0..left.len() {
-                        let mut cut = Tag::new("CUT");
-                        cut.set("CLength", Variant::Float(length as f32));
+    /// Template rendering the jobs of `source` : the one of `template_name`
+    /// for API projects and machines, none for other files which start from
+    /// fixed tags.
+    pub(crate) fn template(
+        &self,
+        source: &Source,
+        options: &ExportOptions,
+    ) -> Result<Option<Template>> {
+        match (source, &options.machine) {
+            (Source::File(_), None) => Ok(None),
+            _ => Ok(Some(self.templates.load(&Self::template_name(options))?)),
+        }
+    }
 
-                        let cut_index = cut_tags.len() + 1;
-                        cut.set("CNo", Variant::Int(cut_index as i32));
+    /// Serialized jobs of `source`, named after `project` and rendered from
+    /// `template`.
+    pub(crate) fn documents(
+        &self,
+        source: &Source,
+        template: Option<&Template>,
+        project: &str,
+        options: &ExportOptions,
+    ) -> Result<Vec<(String, String)>> {
+        if let Source::File(file) = source {
+            let path = Path::new(file);
+            if !path.is_file() {
+                return Err(anyhow!("Source must be a file"));
+            }
+        }
 
-                        let angles = &left[i];
-                        cut.set(
-                            "CAngleLH",
-                            Variant::Float(angles["h"]["value"].as_f64().unwrap() as f32),
-                        );
-                        cut.set(
-                            "CAngleLV",
-                            Variant::Float(angles["v"]["value"].as_f64().unwrap() as f32),
-                        );
+        // Attributes set on every job, over the template
+        let mut settings = vec![];
 
-                        let angles = &right[i];
-                        cut.set(
-                            "CAngleRH",
-                            Variant::Float(angles["h"]["value"].as_f64().unwrap() as f32),
-                        );
-                        cut.set(
-                            "CAngleRV",
-                            Variant::Float(angles["v"]["value"].as_f64().unwrap() as f32),
-                        );
+        let mut exporter = self.clone();
+        exporter.template = template.cloned();
+        let parts = match source {
+            Source::Api { data, .. } => {
+                settings.extend(
+                    Self::macros(data)?
+                        .into_iter()
+                        .map(|(attr, value)| ("JOB", attr, value)),
+                );
 
-                        cut.set("CRotation", Variant::Float(0.0));
-                        cut.set("CSawRotation", Variant::Float(0.0));
+                Part::from_source(source)?
+            }
 
-                        cut_tags.push(cut);
-                    }
-                }
+            Source::File(_) => {
+                exporter.tags.clear();
+                exporter.set_attribute("OPTIONS", "OScale", Variant::Int(1));
+                exporter.set_attribute("OPTIONS", "OCreator", Variant::String(CREATOR.to_owned()));
 
-                _ => {
-                    // à voir
-                }
+                Part::from_source(source)?
             }
-        }
+        };
 
-        let cut_count = cut_tags.len() as i32;
-        for mut tag in cut_tags {
-            tag.set("CCount", Variant::Int(cut_count));
-            self.tags.push(tag);
+        if let Some(machine) = &options.machine {
+            if let Some(vendor_id) = machine.vendor_id {
+                settings.push(("OPTIONS", "OVendorID".to_owned(), Variant::Int(vendor_id)));
+            }
+            if let Some(driver) = &machine.driver {
+                settings.push((
+                    "JOB",
+                    "cncdriver".to_owned(),
+                    Variant::String(driver.clone()),
+                ));
+            }
         }
 
-        Ok(())
-    }
-
-    fn to_string(&self) -> String {
-        let mut serialized = String::new();
-
-        for tag in &self.tags {
-            serialized += &tag.to_string();
-        }
+        Ok(exporter
+            .jobs(parts, project, options)?
+            .into_iter()
+            .map(|(name, mut job)| {
+                for (tag, attr, value) in &settings {
+                    job.set_attribute(tag, attr, value.clone());
+                }
 
-        serialized
+                (name, job.to_string())
+            })
+            .collect())
     }
 
-    fn from_template() -> Result<Self> {
-        let template = Self::load_template()?;
-        Self::read_template(&template)
+    #[cfg(test)]
+    fn read_template(template: &str) -> Result<Self> {
+        Ok(Self {
+            tags: read_tags(template)?,
+            ..Default::default()
+        })
     }
+}
 
-    fn load_template() -> Result<String> {
-        let template_path = std::env::var("TEMPLATE_PATH")?;
-        let path = Path::new(&template_path).join("elumatec");
-
-        let mut file = File::open(path)?;
-        let mut buffer = String::new();
-        file.read_to_string(&mut buffer)?;
-
-        Ok(buffer)
-    }
+/// Parses `:TAG` headers followed by `key = value` lines.
+pub(crate) fn read_tags(template: &str) -> Result<Vec<Tag>> {
+    let buffer = template.to_owned();
 
-    fn read_template(template: &str) -> Result<Self> {
-        let buffer = template.to_owned();
+    let mut tags = vec![];
+    let mut curr_tag: Option<Tag> = None;
 
-        let mut tags = vec![];
-        let mut curr_tag: Option<Tag> = None;
+    for (line_index, line) in buffer.split("\n").enumerate() {
+        // Check comments
+        let line = line.split("//").next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
 
-        for (line_index, line) in buffer.split("\n").enumerate() {
-            // Check comments
-            let line = line.split("//").next().unwrap_or_default().trim();
-            if line.is_empty() {
-                continue;
+        if line.as_bytes()[0] == b':' {
+            if let Some(tag) = &curr_tag {
+                tags.push(tag.clone());
             }
 
-            if line.as_bytes()[0] == b':' {
-                if let Some(tag) = &curr_tag {
-                    tags.push(tag.clone());
-                }
-
-                curr_tag = Some(Tag::new(&line[1..]));
-            } else if let Some(tag) = &mut curr_tag {
-                if tag.update_attributes(line).is_none() {
-                    return Err(anyhow!(
-                        "{line}\n^ Invalid syntax on line {} : unable to read template file",
-                        line_index + 1
-                    ));
-                }
+            curr_tag = Some(Tag::new(&line[1..]));
+        } else if let Some(tag) = &mut curr_tag {
+            if tag.update_attributes(line).is_none() {
+                return Err(anyhow!(
+                    "{line}\n^ Invalid syntax on line {} : unable to read template file",
+                    line_index + 1
+                ));
             }
         }
+    }
 
-        if let Some(tag) = curr_tag {
-            tags.push(tag);
-        }
-
-        Ok(Self { tags })
+    if let Some(tag) = curr_tag {
+        tags.push(tag);
     }
+
+    Ok(tags)
 }
 
-impl Default for ElumatecExporter {
-    fn default() -> Self {
-        ElumatecExporter { tags: vec![] }
+impl Display for ElumatecExporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for tag in &self.tags {
+            write!(f, "{tag}")?;
+        }
+
+        Ok(())
     }
 }
 
@@ -380,35 +351,42 @@ impl Export for ElumatecExporter {
         "ncw".to_owned()
     }
 
-    fn export(&self, source: Source, output_path: Option<String>) -> Result<()> {
-        if let Source::File(file) = &source {
-            let path = Path::new(file);
-            if !path.is_file() {
-                return Err(anyhow!("Source must be a file"));
-            }
-        }
-
-        let mut exporter = self.clone();
-
-        match source {
-            Source::Api { project_uuid } => {
-                exporter.update_from_api(&project_uuid)?;
+    fn export(
+        &self,
+        source: Source,
+        output_path: Option<String>,
+        options: &ExportOptions,
+    ) -> Result<ExportOutput> {
+        let project = project_name(&source, &output_path);
+        let template = self.template(&source, options)?;
+        let jobs = self.documents(&source, template.as_ref(), &project, options)?;
+
+        if let Some(machine) = &options.machine {
+            let mut issues = vec![];
+            for (name, job) in &jobs {
+                issues.extend(
+                    machine
+                        .validate(job)?
+                        .into_iter()
+                        .map(|issue| format!("{name} : {issue}")),
+                );
             }
 
-            Source::File(path) => {
-                exporter.tags.clear();
-                exporter.update_from_file(&path)?;
+            if !issues.is_empty() {
+                return Err(anyhow!(
+                    "Jobs out of the limits of machine `{}` :\n{}",
+                    machine.name,
+                    issues.join("\n")
+                ));
             }
         }
 
-        let serialized = exporter.to_string();
-        if let Some(output_path) = output_path {
-            fs::write(output_path, serialized)?;
-        } else {
-            println!("{serialized}");
-        }
+        clamp::verify(&jobs)?;
 
-        Ok(())
+        Ok(ExportOutput::new(
+            write_jobs(jobs, &output_path, &self.extension())?,
+            template.as_ref(),
+        ))
     }
 }
 
@@ -417,6 +395,7 @@ mod tests {
     use tests::variant::Variant;
 
     use super::*;
+    use crate::{part::Extremity, template::Origin, Machine, PartRefs, Split};
 
     #[test]
     fn serialize() {
@@ -491,4 +470,190 @@ mod tests {
         let tag = &exporter.tags[0];
         assert_eq!(tag.get("Int"), Some(Variant::Int(0)));
     }
+
+    #[test]
+    fn name_jobs_after_split_references() {
+        let part = |profile: &str| Part {
+            refs: PartRefs {
+                view: "F1".to_owned(),
+                profile: profile.to_owned(),
+                bar: "1".to_owned(),
+            },
+            length: 1000.0,
+            left: Extremity::default(),
+            right: Extremity::default(),
+            quantity: 1,
+            number: 1,
+            comment: "F1".to_owned(),
+            ..Default::default()
+        };
+        let parts = vec![part("L100"), part("L200"), part("L100")];
+
+        let options = ExportOptions {
+            split: Split::Profile,
+            ..Default::default()
+        };
+        let jobs = ElumatecExporter::default()
+            .jobs(parts, "project", &options)
+            .unwrap();
+        let names = jobs
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["project_L100", "project_L200"]);
+
+        let cut = &jobs[0].1.tags[1];
+        assert_eq!(cut.get("CComNo"), Some(Variant::String("F1".to_owned())));
+        assert_eq!(cut.get("CNo"), Some(Variant::Int(2)));
+        assert_eq!(cut.get("CCount"), Some(Variant::Int(2)));
+    }
+
+    #[test]
+    fn render_cuts_from_template() {
+        let exporter = ElumatecExporter {
+            template: Some(Template {
+                name: "elumatec".to_owned(),
+                origin: Origin::Builtin,
+                contents: r#"
+:OPTIONS
+OCreator = "{{ creator }}"
+:JOB
+info = "{{ job }} : {{ len(parts) }} cuts"
+{% for part in parts %}
+:CUT
+CNo = {{ loop.index }}
+CLength = {{ part.length }}
+CAngleLH = {{ part.left.h }}
+{% if part.left.h != 90 %}
+CComNo = "{{ part.refs.profile }} mitre"
+{% endif %}
+{% endfor %}
+"#
+                .to_owned(),
+                includes: vec![],
+                base: None,
+            }),
+            ..Default::default()
+        };
+
+        let part = |length: f32, h: f32| Part {
+            refs: PartRefs {
+                profile: "L100".to_owned(),
+                ..Default::default()
+            },
+            length,
+            left: Extremity {
+                h,
+                v: 90.0,
+                z: None,
+            },
+            ..Default::default()
+        };
+
+        let jobs = exporter
+            .jobs(
+                vec![part(1000.0, 90.0), part(78.47212, 45.0)],
+                "project",
+                &ExportOptions::default(),
+            )
+            .unwrap();
+        let tags = &jobs[0].1.tags;
+
+        assert_eq!(tags.len(), 4);
+        assert_eq!(
+            tags[0].get("OCreator"),
+            Some(Variant::String("Elucad".to_owned()))
+        );
+        assert_eq!(
+            tags[1].get("info"),
+            Some(Variant::String("project : 2 cuts".to_owned()))
+        );
+        assert_eq!(tags[2].get("CLength"), Some(Variant::Int(1000)));
+        assert_eq!(tags[2].get("CComNo"), None);
+        assert_eq!(tags[3].get("CNo"), Some(Variant::Int(2)));
+        assert_eq!(tags[3].get("CLength"), Some(Variant::Float(78.47212)));
+        assert_eq!(
+            tags[3].get("CComNo"),
+            Some(Variant::String("L100 mitre".to_owned()))
+        );
+    }
+
+    #[test]
+    fn number_cuts_per_job() {
+        let mut exporter = ElumatecExporter::default();
+        exporter.update_cuts(vec![Tag::new("CUT"), Tag::new("CUT")]);
+
+        assert_eq!(exporter.tags[1].get("CNo"), Some(Variant::Int(2)));
+        assert_eq!(exporter.tags[1].get("CCount"), Some(Variant::Int(2)));
+    }
+
+    #[test]
+    fn write_machine_settings() {
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::write(
+            temp.path().join("sbz151"),
+            ":OPTIONS\nOScale = 1\n\n:JOB\ninfo = \"SBZ151\"\n",
+        )
+        .unwrap();
+
+        let options = ExportOptions {
+            machine: Some(Machine {
+                name: "SBZ151".to_owned(),
+                template: "sbz151".to_owned(),
+                vendor_id: Some(1),
+                driver: Some("1.2".to_owned()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let source = Source::File(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/dstv/steel.json").to_owned(),
+        );
+
+        // Files are rendered from the template of the machine too
+        let exporter = ElumatecExporter::new(&Templates {
+            paths: vec![temp.path().to_path_buf()],
+        });
+        let template = exporter.template(&source, &options).unwrap();
+        let jobs = exporter
+            .documents(&source, template.as_ref(), "steel", &options)
+            .unwrap();
+
+        assert!(jobs[0].1.contains("info\t=\t\"SBZ151\"\n"));
+        assert!(jobs[0].1.contains("OVendorID\t=\t1\n"));
+        assert!(jobs[0].1.contains(":JOB\ncncdriver\t=\t\"1.2\"\n"));
+    }
+
+    #[test]
+    fn export_api_project() {
+        let client = crate::util::tests::client();
+        let project_uuid = "0488bf92-813f-4bbd-8e5f-16885d5b75df".to_owned();
+        let source = Source::Api {
+            data: client.project_data(&project_uuid).unwrap(),
+            project_uuid,
+        };
+
+        let exporter = ElumatecExporter::default();
+        let options = ExportOptions::default();
+        let template = exporter.template(&source, &options).unwrap();
+        let jobs = exporter
+            .documents(&source, template.as_ref(), "import", &options)
+            .unwrap();
+        assert_eq!(jobs.len(), 1);
+
+        let job = &jobs[0].1;
+        assert!(job.contains("Var0\t=\t12.5\n"));
+        assert!(job.contains("Var1\t=\t40\n"));
+        assert_eq!(job.matches(":CUT\n").count(), 2);
+        assert!(job.contains("CLength\t=\t800\n"));
+
+        // Unreadable projects are not exported as empty jobs
+        let source = Source::Api {
+            data: ProjectData::default(),
+            project_uuid: "p1".to_owned(),
+        };
+        assert!(exporter
+            .documents(&source, template.as_ref(), "p1", &options)
+            .is_err());
+    }
 }
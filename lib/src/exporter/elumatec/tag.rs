@@ -1,6 +1,6 @@
 use super::variant::Variant;
 use human_sort::sort;
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt::Display};
 
 #[derive(Clone)]
 pub struct Tag {
@@ -18,7 +18,7 @@ impl Tag {
     }
 
     pub fn get(&self, attr: &str) -> Option<Variant> {
-        self.attributes.get(attr).map(|value| value.clone())
+        self.attributes.get(attr).cloned()
     }
 
     pub fn set(&mut self, attr: &str, value: Variant) {
@@ -29,24 +29,9 @@ impl Tag {
         self.attributes.is_empty()
     }
 
-    pub fn to_string(&self) -> String {
-        let mut serialized = format!(":{}\n", self.name);
-
-        let mut keys = self
-            .attributes
-            .keys()
-            .map(|key| key.as_str())
-            .collect::<Vec<_>>();
-        let mut keys = keys.as_mut_slice();
-        sort(&mut keys);
-
-        for key in keys {
-            let value = self.get(key).unwrap();
-            serialized += &format!("{key}\t=\t{value}\n");
-        }
-        serialized += "\n";
-
-        serialized
+    /// Sets every attribute of `other`, keeping the others.
+    pub fn merge(&mut self, other: Tag) {
+        self.attributes.extend(other.attributes);
     }
 
     pub fn update_attributes(&mut self, line: &str) -> Option<(String, Variant)> {
@@ -72,6 +57,26 @@ impl Tag {
     }
 }
 
+impl Display for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, ":{}", self.name)?;
+
+        let mut keys = self
+            .attributes
+            .keys()
+            .map(|key| key.as_str())
+            .collect::<Vec<_>>();
+        sort(keys.as_mut_slice());
+
+        for key in keys {
+            let value = &self.attributes[key];
+            writeln!(f, "{key}\t=\t{value}")?;
+        }
+
+        writeln!(f)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
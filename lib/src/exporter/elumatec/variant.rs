@@ -8,20 +8,14 @@ pub enum Variant {
     Null,
 }
 
-impl Variant {
-    pub fn to_string(&self) -> String {
-        match self {
-            Self::Int(int) => format!("{int}"),
-            Self::Float(float) => format!("{float}"),
-            Self::String(string) => format!("\"{string}\""),
-            Self::Null => String::new(),
-        }
-    }
-}
-
 impl Display for Variant {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.to_string())
+        match self {
+            Self::Int(int) => write!(f, "{int}"),
+            Self::Float(float) => write!(f, "{float}"),
+            Self::String(string) => write!(f, "\"{string}\""),
+            Self::Null => Ok(()),
+        }
     }
 }
 
@@ -0,0 +1,602 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+use super::{
+    elumatec::{tag::Tag, variant::Variant},
+    project_name, write_jobs,
+};
+use crate::{
+    config::Templates,
+    part::{Face, Machining, MachiningKind, Part},
+    Export, ExportOptions, ExportOutput, Source,
+};
+
+/// Codes of the targeted controller, read from the `:POST` tag of the template.
+#[derive(Clone)]
+struct Post {
+    program_start: String,
+    program_number: String,
+    setup: String,
+    line_numbers: bool,
+    decimals: usize,
+    safe_z: f32,
+    clearance_z: f32,
+
+    /// Length of the moves switching cutter compensation on and off.
+    lead: f32,
+    tool_change: String,
+    spindle_on: String,
+    spindle_off: String,
+    comp_left: String,
+    comp_cancel: String,
+    turn: String,
+    program_end: String,
+}
+
+impl Default for Post {
+    fn default() -> Self {
+        Post {
+            program_start: "%".to_owned(),
+            program_number: "O1000".to_owned(),
+            setup: "G21 G17 G40 G49 G80 G90".to_owned(),
+            line_numbers: true,
+            decimals: 3,
+            safe_z: 50.0,
+            clearance_z: 5.0,
+            lead: 10.0,
+            tool_change: "T{tool} M06".to_owned(),
+            spindle_on: "S{speed} M03".to_owned(),
+            spindle_off: "M05".to_owned(),
+            comp_left: "G41 D{tool}".to_owned(),
+            comp_cancel: "G40".to_owned(),
+            turn: "M00".to_owned(),
+            program_end: "M30".to_owned(),
+        }
+    }
+}
+
+impl Post {
+    fn from_tag(tag: &Tag) -> Self {
+        let default = Self::default();
+        let text = |attr: &str, default: String| match tag.get(attr) {
+            Some(Variant::String(value)) => value,
+            Some(Variant::Null) => String::new(),
+            Some(value) => value.to_string(),
+            None => default,
+        };
+
+        Post {
+            program_start: text("ProgramStart", default.program_start),
+            program_number: text("ProgramNumber", default.program_number),
+            setup: text("Setup", default.setup),
+            line_numbers: number(tag, "LineNumbers").map_or(default.line_numbers, |n| n != 0.0),
+            decimals: number(tag, "Decimals").map_or(default.decimals, |n| n as usize),
+            safe_z: number(tag, "SafeZ").unwrap_or(default.safe_z),
+            clearance_z: number(tag, "ClearanceZ").unwrap_or(default.clearance_z),
+            lead: number(tag, "Lead").unwrap_or(default.lead),
+            tool_change: text("ToolChange", default.tool_change),
+            spindle_on: text("SpindleOn", default.spindle_on),
+            spindle_off: text("SpindleOff", default.spindle_off),
+            comp_left: text("CompLeft", default.comp_left),
+            comp_cancel: text("CompCancel", default.comp_cancel),
+            turn: text("Turn", default.turn),
+            program_end: text("ProgramEnd", default.program_end),
+        }
+    }
+}
+
+/// Entry of the tool library, read from the `:TOOL` tags of the template.
+#[derive(Clone, Debug, PartialEq)]
+struct Tool {
+    id: String,
+    number: u32,
+    diameter: f32,
+    speed: f32,
+    feed: f32,
+    plunge_feed: f32,
+}
+
+impl Tool {
+    fn from_tag(tag: &Tag) -> Result<Self> {
+        let id = match tag.get("Id") {
+            Some(Variant::String(id)) => id,
+            Some(Variant::Int(id)) => id.to_string(),
+            _ => return Err(anyhow!("Tool without `Id` in post-processor template")),
+        };
+
+        let required = |attr: &str| {
+            number(tag, attr).ok_or(anyhow!(
+                "Tool `{id}` has no `{attr}` in post-processor template"
+            ))
+        };
+
+        let feed = required("Feed")?;
+        Ok(Tool {
+            number: required("Number")? as u32,
+            diameter: required("Diameter")?,
+            speed: required("Speed")?,
+            plunge_feed: number(tag, "PlungeFeed").unwrap_or(feed),
+            feed,
+            id,
+        })
+    }
+}
+
+fn number(tag: &Tag, attr: &str) -> Option<f32> {
+    match tag.get(attr)? {
+        Variant::Int(value) => Some(value as f32),
+        Variant::Float(value) => Some(value),
+        _ => None,
+    }
+}
+
+/// ISO (Fanuc style) programs for 3-axis machining centers, one per part.
+#[derive(Clone, Default)]
+pub struct GcodeExporter {
+    post: Post,
+    tools: Vec<Tool>,
+    templates: Templates,
+}
+
+impl GcodeExporter {
+    /// The post-processor is read from the `gcode` template when exporting.
+    pub fn new(templates: &Templates) -> Self {
+        Self {
+            templates: templates.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn from_tags(tags: Vec<Tag>) -> Result<Self> {
+        let mut exporter = Self::default();
+
+        for tag in tags {
+            match tag.name.as_str() {
+                "POST" => exporter.post = Post::from_tag(&tag),
+                "TOOL" => exporter.tools.push(Tool::from_tag(&tag)?),
+                _ => {}
+            }
+        }
+
+        Ok(exporter)
+    }
+
+    /// Tool named by the machining, or the one matching its diameter.
+    fn tool(&self, machining: &Machining) -> Result<&Tool> {
+        if !machining.tool.is_empty() {
+            return self
+                .tools
+                .iter()
+                .find(|tool| tool.id == machining.tool)
+                .ok_or(anyhow!(
+                    "Tool `{}` is not in the tool library",
+                    machining.tool
+                ));
+        }
+
+        match machining.kind {
+            MachiningKind::Contour => self
+                .tools
+                .iter()
+                .min_by(|a, b| a.diameter.total_cmp(&b.diameter)),
+            _ => self
+                .tools
+                .iter()
+                .find(|tool| (tool.diameter - machining.diameter).abs() < 0.01),
+        }
+        .ok_or(anyhow!(
+            "No tool of diameter {} in the tool library",
+            machining.diameter
+        ))
+    }
+
+    fn serialize(&self, part: &Part, project: &str) -> Result<String> {
+        let mut program = Program::new(&self.post);
+        program.raw(&self.post.program_start);
+        program.raw(&format!(
+            "{} ({project} PART {})",
+            self.post.program_number, part.number
+        ));
+        program.line(&self.post.setup);
+
+        let mut current_tool: Option<&Tool> = None;
+        let faces = [Face::Front, Face::Top, Face::Bottom, Face::Back];
+
+        for (face_index, face) in faces.iter().enumerate() {
+            let machinings = part
+                .machinings
+                .iter()
+                .filter(|machining| machining.face == *face)
+                .collect::<Vec<_>>();
+
+            if machinings.is_empty() {
+                continue;
+            }
+
+            // The part is set up with its front face up
+            if face_index > 0 {
+                program.rapid_z(self.post.safe_z);
+                if current_tool.is_some() {
+                    program.line(&self.post.spindle_off);
+                }
+                program.comment(&format!("TURN PART, {face:?} FACE UP").to_uppercase());
+                program.line(&self.post.turn);
+                current_tool = None;
+            }
+
+            let thickness = match face {
+                Face::Front | Face::Back => part.section.width,
+                Face::Top | Face::Bottom => part.section.height,
+            };
+
+            for machining in machinings {
+                let tool = self.tool(machining)?;
+                if current_tool != Some(tool) {
+                    program.rapid_z(self.post.safe_z);
+                    if current_tool.is_some() {
+                        program.line(&self.post.spindle_off);
+                    }
+
+                    program.comment(&format!("TOOL {} D{}", tool.id, tool.diameter));
+                    program.line(&tool_code(&self.post.tool_change, tool));
+                    program.line(&tool_code(&self.post.spindle_on, tool));
+                    current_tool = Some(tool);
+                }
+
+                let depth = match machining.depth {
+                    Some(depth) => depth,
+                    None if thickness > 0.0 => thickness + 1.0,
+                    None => {
+                        return Err(anyhow!(
+                            "Unable to machine through part {} : unknown section thickness",
+                            part.number
+                        ))
+                    }
+                };
+
+                program.machining(machining, tool, depth);
+            }
+        }
+
+        program.rapid_z(self.post.safe_z);
+        program.line(&self.post.spindle_off);
+        program.line(&self.post.program_end);
+        program.raw(&self.post.program_start);
+
+        Ok(program.lines.join("\n") + "\n")
+    }
+}
+
+fn tool_code(code: &str, tool: &Tool) -> String {
+    code.replace("{tool}", &tool.number.to_string())
+        .replace("{speed}", &tool.speed.to_string())
+        .replace("{feed}", &tool.feed.to_string())
+}
+
+/// Program being written, numbering its blocks.
+struct Program<'a> {
+    post: &'a Post,
+    lines: Vec<String>,
+    block_number: u32,
+}
+
+impl<'a> Program<'a> {
+    fn new(post: &'a Post) -> Self {
+        Program {
+            post,
+            lines: vec![],
+            block_number: 0,
+        }
+    }
+
+    fn raw(&mut self, line: &str) {
+        if !line.is_empty() {
+            self.lines.push(line.to_owned());
+        }
+    }
+
+    fn line(&mut self, line: &str) {
+        if line.is_empty() {
+            return;
+        }
+
+        if self.post.line_numbers {
+            self.block_number += 10;
+            self.lines.push(format!("N{} {line}", self.block_number));
+        } else {
+            self.lines.push(line.to_owned());
+        }
+    }
+
+    fn comment(&mut self, comment: &str) {
+        self.line(&format!("({comment})"));
+    }
+
+    fn number(&self, value: f32) -> String {
+        format!("{value:.*}", self.post.decimals)
+    }
+
+    fn rapid_z(&mut self, z: f32) {
+        self.line(&format!("G0 Z{}", self.number(z)));
+    }
+
+    fn rapid_xy(&mut self, x: f32, y: f32) {
+        self.line(&format!("G0 X{} Y{}", self.number(x), self.number(y)));
+    }
+
+    fn machining(&mut self, machining: &Machining, tool: &Tool, depth: f32) {
+        let clearance = self.post.clearance_z;
+        let (x, y) = (machining.x, machining.y);
+
+        match machining.kind {
+            MachiningKind::Drilling => {
+                self.rapid_xy(x, y);
+                self.rapid_z(clearance);
+                self.line(&format!(
+                    "G81 X{} Y{} Z{} R{} F{}",
+                    self.number(x),
+                    self.number(y),
+                    self.number(-depth),
+                    self.number(clearance),
+                    self.number(tool.plunge_feed)
+                ));
+                self.line("G80");
+            }
+
+            MachiningKind::Slot => {
+                self.rapid_xy(x, y);
+                self.rapid_z(clearance);
+                self.line(&format!(
+                    "G1 Z{} F{}",
+                    self.number(-depth),
+                    self.number(tool.plunge_feed)
+                ));
+                self.line(&format!(
+                    "G1 X{} F{}",
+                    self.number(x + machining.length),
+                    self.number(tool.feed)
+                ));
+                self.rapid_z(clearance);
+            }
+
+            MachiningKind::Contour => {
+                let Some(&start) = machining.points.first() else {
+                    return;
+                };
+
+                // Unit vector from `from` to `to`, if they differ
+                let direction = |from: (f32, f32), to: (f32, f32)| {
+                    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+                    let length = dx.hypot(dy);
+                    (length > 0.0).then(|| (dx / length, dy / length))
+                };
+                let Some(first) = machining.points.iter().find_map(|&to| direction(start, to))
+                else {
+                    return;
+                };
+                let last = machining
+                    .points
+                    .iter()
+                    .rev()
+                    .find_map(|&from| direction(from, start))
+                    .unwrap_or(first);
+
+                // Leads start and end off the contour, on the side of the tool
+                // (left of the moves), so that compensation is switched on and
+                // off away from the part
+                let lead = self.post.lead;
+                let lead_in = (start.0 - first.1 * lead, start.1 + first.0 * lead);
+                let lead_out = (start.0 - last.1 * lead, start.1 + last.0 * lead);
+
+                self.rapid_xy(x + lead_in.0, y + lead_in.1);
+                self.rapid_z(clearance);
+                self.line(&format!(
+                    "G1 Z{} F{}",
+                    self.number(-depth),
+                    self.number(tool.plunge_feed)
+                ));
+
+                let move_to = |program: &Self, code: &str, (point_x, point_y): (f32, f32)| {
+                    format!(
+                        "{code} G1 X{} Y{}",
+                        program.number(x + point_x),
+                        program.number(y + point_y)
+                    )
+                    .trim_start()
+                    .to_owned()
+                };
+
+                let comp_left = tool_code(&self.post.comp_left, tool);
+                let line = move_to(self, &comp_left, start);
+                self.line(&format!("{line} F{}", self.number(tool.feed)));
+
+                for &point in machining.points.iter().skip(1).chain([&start]) {
+                    let line = move_to(self, "", point);
+                    self.line(&line);
+                }
+
+                let line = move_to(self, &self.post.comp_cancel, lead_out);
+                self.line(&line);
+                self.rapid_z(clearance);
+            }
+        }
+    }
+}
+
+impl Export for GcodeExporter {
+    fn extension(&self) -> String {
+        "nc".to_owned()
+    }
+
+    fn export(
+        &self,
+        source: Source,
+        output_path: Option<String>,
+        options: &ExportOptions,
+    ) -> Result<ExportOutput> {
+        if let Source::File(file) = &source {
+            if !Path::new(file).is_file() {
+                return Err(anyhow!("Source must be a file"));
+            }
+        }
+
+        let template = self.templates.load("gcode")?;
+        let post = Self::from_tags(template.tags(&Value::Null, &["POST"])?)?;
+        let parts = Part::from_source(&source)?;
+        let project = project_name(&source, &output_path);
+
+        let mut jobs = vec![];
+        for part in parts {
+            if part.machinings.is_empty() {
+                continue;
+            }
+
+            let name = options.file_name(&project, &options.split.key(&part.refs)?);
+            jobs.push((
+                format!("{name}_{}", part.number),
+                post.serialize(&part, &project)?,
+            ));
+        }
+
+        if jobs.is_empty() {
+            eprintln!("No machining to export");
+        }
+
+        Ok(ExportOutput::new(
+            write_jobs(jobs, &output_path, &self.extension())?,
+            Some(&template),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{exporter::elumatec::read_tags, part::Section};
+
+    fn exporter() -> GcodeExporter {
+        GcodeExporter::from_tags(
+            read_tags(
+                r#"
+            :POST
+            LineNumbers = 0
+            Decimals = 1
+            Turn = "M01"
+
+            :TOOL
+            Id = "D8"
+            Number = 1
+            Diameter = 8
+            Speed = 3000
+            Feed = 300
+            PlungeFeed = 150
+
+            :TOOL
+            Id = "FR5K"
+            Number = 2
+            Diameter = 5
+            Speed = 18000
+            Feed = 1200
+        "#,
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn read_post_processor() {
+        let exporter = exporter();
+        assert!(!exporter.post.line_numbers);
+        assert_eq!(exporter.post.turn, "M01");
+        assert_eq!(exporter.post.safe_z, 50.0);
+        assert_eq!(exporter.post.lead, 10.0);
+        assert_eq!(exporter.tools.len(), 2);
+        assert_eq!(exporter.tools[1].plunge_feed, 1200.0);
+    }
+
+    #[test]
+    fn serialize_operations() {
+        let part = Part {
+            number: 1,
+            section: Section {
+                width: 40.0,
+                height: 60.0,
+                ..Default::default()
+            },
+            machinings: vec![
+                Machining {
+                    x: 100.0,
+                    y: 20.0,
+                    diameter: 8.0,
+                    ..Default::default()
+                },
+                Machining {
+                    kind: MachiningKind::Contour,
+                    face: Face::Top,
+                    x: 500.0,
+                    y: 10.0,
+                    depth: Some(3.0),
+                    points: vec![(0.0, 0.0), (50.0, 0.0), (50.0, 20.0)],
+                    tool: "FR5K".to_owned(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let program = exporter().serialize(&part, "project").unwrap();
+        let expected = [
+            "%",
+            "O1000 (project PART 1)",
+            "G21 G17 G40 G49 G80 G90",
+            "G0 Z50.0",
+            "(TOOL D8 D8)",
+            "T1 M06",
+            "S3000 M03",
+            "G0 X100.0 Y20.0",
+            "G0 Z5.0",
+            "G81 X100.0 Y20.0 Z-41.0 R5.0 F150.0",
+            "G80",
+            "G0 Z50.0",
+            "M05",
+            "(TURN PART, TOP FACE UP)",
+            "M01",
+            "G0 Z50.0",
+            "(TOOL FR5K D5)",
+            "T2 M06",
+            "S18000 M03",
+            "G0 X500.0 Y20.0",
+            "G0 Z5.0",
+            "G1 Z-3.0 F1200.0",
+            "G41 D2 G1 X500.0 Y10.0 F1200.0",
+            "G1 X550.0 Y10.0",
+            "G1 X550.0 Y30.0",
+            "G1 X500.0 Y10.0",
+            "G40 G1 X503.7 Y0.7",
+            "G0 Z5.0",
+            "G0 Z50.0",
+            "M05",
+            "M30",
+            "%",
+        ];
+
+        assert_eq!(program.lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn missing_tool() {
+        let part = Part {
+            machinings: vec![Machining {
+                diameter: 12.0,
+                depth: Some(5.0),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        assert!(exporter().serialize(&part, "project").is_err());
+    }
+}
@@ -1,15 +1,160 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+pub mod bom;
+pub mod cache;
+mod clamp;
+pub mod config;
+pub mod cover;
+mod document;
 mod exporter;
+pub mod machine;
+pub mod mesh;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+pub mod part;
+pub mod preview;
+pub mod template;
 pub mod util;
+pub mod watch;
 
+pub use bom::BomFormat;
+pub use config::Config;
+pub use cover::{ApiError, CoverClient};
 pub use exporter::Exporter;
+pub use machine::Machine;
+pub use mesh::MeshFormat;
+pub use part::{Part, PartRefs};
 
 pub enum Source {
-    Api { project_uuid: String },
+    /// Project data already fetched from the Cover API.
+    Api {
+        project_uuid: String,
+        data: cover::ProjectData,
+    },
     File(String),
 }
 
+/// How the parts of a project are spread over jobs.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Split {
+    /// A single job for the whole project.
+    #[default]
+    None,
+
+    /// One job per structure view (frame).
+    View,
+
+    /// One job per profile reference.
+    Profile,
+
+    /// One job per optimized bar of each profile.
+    Bar,
+}
+
+impl Split {
+    /// Keeps only the references parts are grouped by.
+    /// Fails when splitting by bar a part whose bar is unknown.
+    pub fn key(&self, refs: &PartRefs) -> anyhow::Result<PartRefs> {
+        Ok(match self {
+            Self::None => PartRefs::default(),
+            Self::View => PartRefs {
+                view: refs.view.clone(),
+                ..Default::default()
+            },
+            Self::Profile => PartRefs {
+                profile: refs.profile.clone(),
+                ..Default::default()
+            },
+            Self::Bar if refs.bar.is_empty() => {
+                return Err(anyhow::anyhow!(
+                    "Unable to split by bar, profile `{}` has no bar",
+                    refs.profile
+                ));
+            }
+            Self::Bar => PartRefs {
+                profile: refs.profile.clone(),
+                bar: refs.bar.clone(),
+                ..Default::default()
+            },
+        })
+    }
+
+    pub fn default_pattern(&self) -> &'static str {
+        match self {
+            Self::None => "{project}",
+            Self::View => "{project}_{view}",
+            Self::Profile => "{project}_{profile}",
+            Self::Bar => "{project}_{profile}_{bar}",
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ExportOptions {
+    pub split: Split,
+
+    /// Output file name, without extension. `{project}`, `{view}`, `{profile}`
+    /// and `{bar}` are replaced by the references of each job.
+    pub name_pattern: Option<String>,
+
+    /// Also write the bill of materials of the project, alongside the machine file.
+    pub bom: Option<BomFormat>,
+
+    /// Elumatec machine the jobs are written for, and validated against.
+    pub machine: Option<Machine>,
+}
+
+impl ExportOptions {
+    pub fn file_name(&self, project: &str, refs: &PartRefs) -> String {
+        let pattern = self
+            .name_pattern
+            .as_deref()
+            .unwrap_or(self.split.default_pattern());
+
+        util::render_file_name(
+            pattern,
+            &[
+                ("project", project),
+                ("view", &refs.view),
+                ("profile", &refs.profile),
+                ("bar", &refs.bar),
+            ],
+        )
+    }
+}
+
+/// Files an export wrote, and the template their jobs were rendered from.
+#[derive(Clone, Debug, Default)]
+pub struct ExportOutput {
+    /// Files written : none when the jobs are printed.
+    pub files: Vec<PathBuf>,
+    pub template: Option<String>,
+
+    /// SHA-256 of the template, as `Template::hash` computes it.
+    pub template_hash: Option<String>,
+}
+
+impl ExportOutput {
+    pub fn new(files: Vec<PathBuf>, template: Option<&template::Template>) -> Self {
+        Self {
+            files,
+            template: template.map(|template| template.name.clone()),
+            template_hash: template.map(template::Template::hash),
+        }
+    }
+}
+
 pub trait Export {
-    fn export(&self, source: Source, output_path: Option<String>) -> anyhow::Result<()>;
+    /// Writes the jobs of `source`.
+    fn export(
+        &self,
+        source: Source,
+        output_path: Option<String>,
+        options: &ExportOptions,
+    ) -> anyhow::Result<ExportOutput>;
 
     fn extension(&self) -> String;
 }
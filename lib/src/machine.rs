@@ -0,0 +1,206 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    document::{number, read_cuts, text},
+    exporter::elumatec::{read_tags, tag::Tag},
+    Config,
+};
+
+/// Elumatec machine profile, read from the `[machines.<name>]` tables of the
+/// configuration or from a `:MACHINE` tag of the `machines` template.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Machine {
+    #[serde(skip_deserializing)]
+    pub name: String,
+    /// Template the jobs of the machine start from.
+    #[serde(default = "default_template")]
+    pub template: String,
+    pub vendor_id: Option<i32>,
+    /// `cncdriver` version of the `:JOB` tag.
+    pub driver: Option<String>,
+    /// Travel limits, in millimeters.
+    pub min_length: Option<f32>,
+    pub max_length: Option<f32>,
+    /// Saw angle range, in degrees.
+    pub min_angle: Option<f32>,
+    pub max_angle: Option<f32>,
+    /// Tools of the magazine, any tool is accepted when empty.
+    pub tools: Vec<String>,
+}
+
+fn default_template() -> String {
+    "elumatec".to_owned()
+}
+
+impl Machine {
+    fn from_tag(tag: &Tag) -> Result<Self> {
+        let name = text(tag, "Name");
+        if name.is_empty() {
+            return Err(anyhow!("Machine without `Name` in machines file"));
+        }
+
+        let template = text(tag, "Template");
+        let driver = text(tag, "cncdriver");
+
+        Ok(Machine {
+            template: if template.is_empty() {
+                default_template()
+            } else {
+                template
+            },
+            vendor_id: number(tag, "OVendorID").map(|id| id as i32),
+            driver: (!driver.is_empty()).then_some(driver),
+            min_length: number(tag, "MinLength"),
+            max_length: number(tag, "MaxLength"),
+            min_angle: number(tag, "MinAngle"),
+            max_angle: number(tag, "MaxAngle"),
+            tools: text(tag, "Tools")
+                .split_whitespace()
+                .map(|tool| tool.to_owned())
+                .collect(),
+            name,
+        })
+    }
+
+    /// Reads the `:MACHINE` tags of a machines file.
+    pub fn read_profiles(machines: &str) -> Result<Vec<Self>> {
+        Self::from_tags(&read_tags(machines)?)
+    }
+
+    fn from_tags(tags: &[Tag]) -> Result<Vec<Self>> {
+        tags.iter()
+            .filter(|tag| tag.name == "MACHINE")
+            .map(Self::from_tag)
+            .collect()
+    }
+
+    /// Loads the profile `name`, from the configuration first, then from the
+    /// `machines` template.
+    pub fn load(config: &Config, name: &str) -> Result<Self> {
+        let mut machines = config
+            .machines
+            .iter()
+            .map(|(name, machine)| Machine {
+                name: name.clone(),
+                ..machine.clone()
+            })
+            .collect::<Vec<_>>();
+
+        let template = config.templates.load("machines")?;
+        machines.extend(Self::from_tags(&template.tags(&Value::Null, &[])?)?);
+
+        let names = machines
+            .iter()
+            .map(|machine| machine.name.clone())
+            .collect::<Vec<_>>();
+
+        machines
+            .into_iter()
+            .find(|machine| machine.name.eq_ignore_ascii_case(name))
+            .ok_or(anyhow!(
+                "Unknown machine `{name}`, available machines : {}",
+                names.join(", ")
+            ))
+    }
+
+    /// Cuts and works of an Elumatec document out of the machine limits.
+    pub(crate) fn validate(&self, document: &str) -> Result<Vec<String>> {
+        let mut issues = vec![];
+
+        for cut in read_cuts(document)? {
+            if let Some(max) = self.max_length.filter(|max| cut.length > *max) {
+                issues.push(format!(
+                    "Cut {} : {} mm is longer than the {max} mm travel",
+                    cut.number, cut.length
+                ));
+            }
+
+            if let Some(min) = self.min_length.filter(|min| cut.length < *min) {
+                issues.push(format!(
+                    "Cut {} : {} mm is shorter than the {min} mm minimum",
+                    cut.number, cut.length
+                ));
+            }
+
+            let (min, max) = (
+                self.min_angle.unwrap_or(0.0),
+                self.max_angle.unwrap_or(180.0),
+            );
+            for angle in [cut.left.0, cut.left.1, cut.right.0, cut.right.1] {
+                if angle < min || angle > max {
+                    issues.push(format!(
+                        "Cut {} : {angle}° angle is out of the {min}° to {max}° range",
+                        cut.number
+                    ));
+                }
+            }
+
+            for work in &cut.works {
+                if !work.tool.is_empty()
+                    && !self.tools.is_empty()
+                    && !self.tools.contains(&work.tool)
+                {
+                    issues.push(format!(
+                        "Cut {} : tool `{}` of work {} is not in the magazine",
+                        cut.number, work.tool, work.number
+                    ));
+                }
+            }
+        }
+
+        Ok(issues)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_machines_file() {
+        let machines = Machine::read_profiles(include_str!("../../templates/machines")).unwrap();
+
+        assert_eq!(machines.len(), 2);
+        assert_eq!(machines[0].name, "SBZ122");
+        assert_eq!(machines[0].template, "elumatec");
+        assert_eq!(machines[0].vendor_id, Some(0));
+        assert_eq!(machines[0].driver.as_deref(), Some("1.1"));
+        assert_eq!(machines[0].max_length, Some(7500.0));
+        assert!(machines[0].tools.contains(&"ZAAG.D=500".to_owned()));
+
+        assert!(Machine::read_profiles(":MACHINE\nTemplate = \"elumatec\"\n").is_err());
+    }
+
+    #[test]
+    fn validate_limits_and_tools() {
+        let machine = Machine {
+            name: "small".to_owned(),
+            max_length: Some(2000.0),
+            min_angle: Some(80.0),
+            max_angle: Some(100.0),
+            tools: vec![
+                "ZAAG.D=500".to_owned(),
+                "ZAAG180".to_owned(),
+                "FR10K".to_owned(),
+            ],
+            ..Default::default()
+        };
+
+        let issues = machine
+            .validate(include_str!("../../examples/elumatec.ncw"))
+            .unwrap();
+
+        assert_eq!(
+            issues[..3],
+            [
+                "Cut 1 : 2003 mm is longer than the 2000 mm travel",
+                "Cut 1 : 78.47212° angle is out of the 80° to 100° range",
+                "Cut 1 : tool `FR5K` of work 5 is not in the magazine",
+            ]
+        );
+        assert!(issues[3..].iter().all(|issue| !issue.contains("FR10K")));
+    }
+}
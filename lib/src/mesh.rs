@@ -0,0 +1,469 @@
+use std::{collections::HashMap, f64::consts::PI};
+
+use anyhow::{anyhow, Result};
+
+use self::csg::{prism, subtract, Polygon, Vector};
+use crate::{
+    config::Templates,
+    document::{documents, read_cuts, Bar, Cut, Work},
+    exporter::write_jobs,
+    part::Face,
+    ExportOptions,
+};
+
+mod csg;
+
+/// Segments of a full circle, for section arcs and holes.
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// Distance machinings start from, outside of their face.
+const APPROACH: f64 = 1.0;
+
+/// Mesh file formats.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MeshFormat {
+    #[default]
+    Stl,
+    Obj,
+}
+
+impl MeshFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            MeshFormat::Stl => "stl",
+            MeshFormat::Obj => "obj",
+        }
+    }
+}
+
+type Point = (f64, f64);
+
+/// Closed outline of a polyline, arcs being approximated by segments.
+fn ring(polyline: &[(f32, f32, f32)]) -> Vec<Point> {
+    let mut points: Vec<Point> = vec![];
+
+    for (index, &(x0, y0, bulge)) in polyline.iter().enumerate() {
+        let (x1, y1, _) = polyline[(index + 1) % polyline.len()];
+        let (x0, y0, x1, y1, bulge) = (x0 as f64, y0 as f64, x1 as f64, y1 as f64, bulge as f64);
+        points.push((x0, y0));
+
+        let chord = (x1 - x0).hypot(y1 - y0);
+        if bulge.abs() < 1e-6 || chord < 1e-6 {
+            continue;
+        }
+
+        // Positive bulges turn counterclockwise, around a center on the left of the chord
+        let offset = chord * (1.0 - bulge * bulge) / (4.0 * bulge);
+        let center = (
+            (x0 + x1) / 2.0 - (y1 - y0) / chord * offset,
+            (y0 + y1) / 2.0 + (x1 - x0) / chord * offset,
+        );
+        let radius = (x0 - center.0).hypot(y0 - center.1);
+        let start = (y0 - center.1).atan2(x0 - center.0);
+        let sweep = 4.0 * bulge.atan();
+
+        let segments = (sweep.abs() / (2.0 * PI) * CIRCLE_SEGMENTS as f64).ceil() as usize;
+        for segment in 1..segments {
+            let angle = start + sweep * segment as f64 / segments as f64;
+            points.push((
+                center.0 + radius * angle.cos(),
+                center.1 + radius * angle.sin(),
+            ));
+        }
+    }
+
+    let close = |a: &Point, b: &Point| (a.0 - b.0).hypot(a.1 - b.1) < 1e-6;
+    points.dedup_by(|a, b| close(a, b));
+    if points.len() > 1 && close(&points[0], &points[points.len() - 1]) {
+        points.pop();
+    }
+
+    points
+}
+
+/// Twice the signed area, positive when counterclockwise.
+fn area(ring: &[Point]) -> f64 {
+    ring.iter()
+        .enumerate()
+        .map(|(index, (x0, y0))| {
+            let (x1, y1) = ring[(index + 1) % ring.len()];
+            x0 * y1 - x1 * y0
+        })
+        .sum()
+}
+
+/// Outline of the section, counterclockwise, and its chambers, clockwise.
+/// Unknown sections are drawn as 50 mm squares.
+fn section(bar: &Bar) -> (Vec<Point>, Vec<Vec<Point>>) {
+    let mut rings = bar
+        .polylines
+        .iter()
+        .map(|polyline| ring(polyline))
+        .filter(|ring| ring.len() >= 3)
+        .collect::<Vec<_>>();
+
+    if rings.is_empty() {
+        let (width, height) = (bar.size(Face::Top) as f64, bar.size(Face::Front) as f64);
+        rings.push(vec![
+            (0.0, 0.0),
+            (width, 0.0),
+            (width, height),
+            (0.0, height),
+        ]);
+    }
+
+    rings.sort_by(|a, b| area(b).abs().total_cmp(&area(a).abs()));
+
+    let mut outline = rings.remove(0);
+    if area(&outline) < 0.0 {
+        outline.reverse();
+    }
+
+    for chamber in &mut rings {
+        if area(chamber) > 0.0 {
+            chamber.reverse();
+        }
+    }
+
+    (outline, rings)
+}
+
+/// Section extruded along x, trimmed by the cut planes. The section x and y
+/// axes are the mesh y and z axes.
+fn bar(cut: &Cut) -> Result<(Vec<Polygon>, [f64; 4])> {
+    let (outline, chambers) = section(&cut.bar);
+
+    let bounds = outline.iter().fold(
+        [f64::MAX, f64::MIN, f64::MAX, f64::MIN],
+        |[min_y, max_y, min_z, max_z], &(y, z)| {
+            [min_y.min(y), max_y.max(y), min_z.min(z), max_z.max(z)]
+        },
+    );
+    let [min_y, _, min_z, _] = bounds;
+
+    let cotangent = |angle: f32| {
+        let angle = (angle as f64).to_radians();
+        angle.cos() / angle.sin()
+    };
+    let (left_h, left_v) = (cotangent(cut.left.0), cotangent(cut.left.1));
+    let (right_h, right_v) = (cotangent(cut.right.0), cotangent(cut.right.1));
+    let length = cut.length as f64;
+
+    let left = |(y, z): Point| Vector::new((z - min_z) * left_h + (y - min_y) * left_v, y, z);
+    let right =
+        |(y, z): Point| Vector::new(length - (z - min_z) * right_h - (y - min_y) * right_v, y, z);
+
+    if outline
+        .iter()
+        .any(|&point| right(point).x - left(point).x < 1e-6)
+    {
+        return Err(anyhow!(
+            "Cut {} is too short for its cut angles",
+            cut.number
+        ));
+    }
+
+    let mut polygons = vec![];
+    for ring in std::iter::once(&outline).chain(&chambers) {
+        for (index, &point) in ring.iter().enumerate() {
+            let next = ring[(index + 1) % ring.len()];
+            polygons.push(Polygon::new(vec![
+                left(point),
+                left(next),
+                right(next),
+                right(point),
+            ]));
+        }
+    }
+
+    let mut points = outline.clone();
+    let mut vertices = outline
+        .iter()
+        .flat_map(|&(y, z)| [y, z])
+        .collect::<Vec<_>>();
+    let mut holes = vec![];
+    for chamber in &chambers {
+        holes.push(points.len());
+        points.extend(chamber);
+        vertices.extend(chamber.iter().flat_map(|&(y, z)| [y, z]));
+    }
+
+    let triangles = earcutr::earcut(&vertices, &holes, 2)
+        .map_err(|err| anyhow!("Unable to triangulate the section : {err:?}"))?;
+
+    for triangle in triangles.chunks_exact(3) {
+        let (a, mut b, mut c) = (
+            points[triangle[0]],
+            points[triangle[1]],
+            points[triangle[2]],
+        );
+        let cross = (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0);
+        if cross.abs() < 1e-9 {
+            continue;
+        }
+        if cross < 0.0 {
+            std::mem::swap(&mut b, &mut c);
+        }
+
+        // Counterclockwise in the section plane faces +x
+        polygons.push(Polygon::new(vec![right(a), right(b), right(c)]));
+        polygons.push(Polygon::new(vec![left(a), left(c), left(b)]));
+    }
+
+    Ok((polygons, bounds))
+}
+
+/// Solid removed by `work`, if it has a shape. Works without depth go through.
+fn cutter(work: &Work, [min_y, max_y, min_z, max_z]: [f64; 4]) -> Option<Vec<Polygon>> {
+    let (x, y) = (work.x as f64, work.y as f64);
+    let (along, across) = (Vector::new(1.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0));
+    let up = Vector::new(0.0, 0.0, 1.0);
+
+    let (center, axis, v) = match work.face {
+        Face::Top => (Vector::new(x, min_y + y, max_z), -up, across),
+        Face::Bottom => (Vector::new(x, min_y + y, min_z), up, across),
+        Face::Front => (Vector::new(x, min_y, max_z - y), across, up),
+        Face::Back => (Vector::new(x, max_y, max_z - y), -across, up),
+    };
+    let through = match work.face {
+        Face::Top | Face::Bottom => max_z - min_z,
+        Face::Front | Face::Back => max_y - min_y,
+    };
+
+    let (diameter, length) = work.shape()?;
+    let (diameter, length) = (diameter as f64, length as f64);
+
+    let depth = if work.depth > 0.0 {
+        work.depth as f64
+    } else {
+        through + APPROACH
+    };
+
+    let angle = (work.angle as f64).to_radians();
+    let direction = along * angle.cos() + v * angle.sin();
+    let normal = along * -angle.sin() + v * angle.cos();
+    let origin = center - axis * APPROACH;
+    let radius = diameter / 2.0;
+    let point = |offset: f64, angle: f64| {
+        origin + direction * (offset + radius * angle.cos()) + normal * (radius * angle.sin())
+    };
+
+    let outline =
+        if length > 0.0 {
+            // Two half circles, `length` apart
+            let half = CIRCLE_SEGMENTS / 2;
+            (0..=half)
+                .map(|segment| point(length / 2.0, -PI / 2.0 + PI * segment as f64 / half as f64))
+                .chain((0..=half).map(|segment| {
+                    point(-length / 2.0, PI / 2.0 + PI * segment as f64 / half as f64)
+                }))
+                .collect::<Vec<_>>()
+        } else {
+            (0..CIRCLE_SEGMENTS)
+                .map(|segment| point(0.0, 2.0 * PI * segment as f64 / CIRCLE_SEGMENTS as f64))
+                .collect()
+        };
+
+    Some(prism(&outline, axis * (depth + APPROACH)))
+}
+
+/// Machined part of `cut`.
+fn solid(cut: &Cut) -> Result<Vec<Polygon>> {
+    let (mut solid, bounds) = bar(cut)?;
+    for cutter in cut.works.iter().filter_map(|work| cutter(work, bounds)) {
+        solid = subtract(solid, cutter);
+    }
+
+    Ok(solid)
+}
+
+fn triangles(polygons: &[Polygon]) -> impl Iterator<Item = (Vector, [Vector; 3])> + '_ {
+    polygons.iter().flat_map(|polygon| {
+        (1..polygon.vertices.len() - 1).map(|index| {
+            (
+                polygon.normal(),
+                [
+                    polygon.vertices[0],
+                    polygon.vertices[index],
+                    polygon.vertices[index + 1],
+                ],
+            )
+        })
+    })
+}
+
+fn to_stl(name: &str, polygons: &[Polygon]) -> String {
+    let mut serialized = format!("solid {name}\n");
+    for (normal, vertices) in triangles(polygons) {
+        serialized += &format!(
+            "  facet normal {:.6} {:.6} {:.6}\n    outer loop\n",
+            normal.x, normal.y, normal.z
+        );
+        for vertex in vertices {
+            serialized += &format!(
+                "      vertex {:.4} {:.4} {:.4}\n",
+                vertex.x, vertex.y, vertex.z
+            );
+        }
+        serialized += "    endloop\n  endfacet\n";
+    }
+    serialized += &format!("endsolid {name}\n");
+
+    serialized
+}
+
+fn to_obj(name: &str, polygons: &[Polygon]) -> String {
+    let mut indices: HashMap<String, usize> = HashMap::new();
+    let (mut vertices, mut faces) = (String::new(), String::new());
+
+    for (_, triangle) in triangles(polygons) {
+        let face = triangle
+            .iter()
+            .map(|vertex| {
+                let key = format!("{:.4} {:.4} {:.4}", vertex.x, vertex.y, vertex.z);
+                let count = indices.len();
+                let index = *indices.entry(key.clone()).or_insert_with(|| {
+                    vertices += &format!("v {key}\n");
+                    count + 1
+                });
+                index.to_string()
+            })
+            .collect::<Vec<_>>();
+
+        faces += &format!("f {}\n", face.join(" "));
+    }
+
+    format!("o {name}\n{vertices}{faces}")
+}
+
+/// Writes a mesh of each cut of an Elumatec document, or of the jobs
+/// generated from a JSON source : the section extruded along the part,
+/// trimmed by the cut planes, minus holes and slots.
+pub fn mesh(
+    source: &str,
+    output_path: Option<String>,
+    format: MeshFormat,
+    options: &ExportOptions,
+    templates: &Templates,
+) -> Result<()> {
+    let mut meshes = vec![];
+    for (name, document) in documents(source, &output_path, options, templates)? {
+        for cut in read_cuts(&document)? {
+            let name = format!("{name}_{}", cut.number);
+            let solid = solid(&cut)?;
+
+            let serialized = match format {
+                MeshFormat::Stl => to_stl(&name, &solid),
+                MeshFormat::Obj => to_obj(&name, &solid),
+            };
+            meshes.push((name, serialized));
+        }
+    }
+
+    write_jobs(meshes, &output_path, format.extension())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::csg::volume;
+
+    fn square_bar(left: f32) -> Cut {
+        Cut {
+            number: 1,
+            part: 1,
+            length: 100.0,
+            left: (left, 90.0),
+            right: (90.0, 90.0),
+            bar: Bar {
+                width: 10.0,
+                height: 10.0,
+                polylines: vec![vec![
+                    (0.0, 0.0, 0.0),
+                    (10.0, 0.0, 0.0),
+                    (10.0, 10.0, 0.0),
+                    (0.0, 10.0, 0.0),
+                ]],
+                ..Default::default()
+            },
+            works: vec![],
+        }
+    }
+
+    fn hole(face: Face, diameter: f32) -> Work {
+        Work {
+            kind: "B".to_owned(),
+            number: 1,
+            face,
+            x: 50.0,
+            y: 5.0,
+            diameter,
+            depth: 0.0,
+            width: 0.0,
+            length: 0.0,
+            angle: 0.0,
+            tool: String::new(),
+            comment: String::new(),
+        }
+    }
+
+    #[test]
+    fn approximate_bulges() {
+        let semicircle = ring(&[(0.0, 0.0, 1.0), (2.0, 0.0, 0.0)]);
+
+        // Both ends, and the points between them
+        assert_eq!(semicircle.len(), CIRCLE_SEGMENTS / 2 + 1);
+        for (x, y) in &semicircle[1..semicircle.len() - 1] {
+            assert!(((x - 1.0).hypot(*y) - 1.0).abs() < 1e-6);
+            assert!(*y < 0.0);
+        }
+    }
+
+    #[test]
+    fn trim_by_cut_planes() {
+        let mitred = solid(&square_bar(45.0)).unwrap();
+        assert!((volume(&mitred) - 9500.0).abs() < 1e-6);
+
+        let mut short = square_bar(5.0);
+        short.length = 50.0;
+        assert!(solid(&short).unwrap_err().to_string().contains("too short"));
+    }
+
+    #[test]
+    fn subtract_through_holes() {
+        let mut cut = square_bar(90.0);
+        cut.works = vec![hole(Face::Top, 4.0), hole(Face::Front, 4.0)];
+        cut.works[1].x = 20.0;
+
+        // Regular polygons approximating the holes
+        let hole_area =
+            CIRCLE_SEGMENTS as f64 / 2.0 * 4.0 * (2.0 * PI / CIRCLE_SEGMENTS as f64).sin();
+        let drilled = solid(&cut).unwrap();
+        assert!((volume(&drilled) - (10000.0 - 2.0 * hole_area * 10.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn serialize_meshes() {
+        let square = solid(&square_bar(90.0)).unwrap();
+
+        let stl = to_stl("bar", &square);
+        assert!(stl.starts_with("solid bar\n  facet normal"));
+        assert_eq!(stl.matches("facet normal").count(), 12);
+
+        let obj = to_obj("bar", &square);
+        assert_eq!(obj.matches("\nv ").count(), 8);
+        assert_eq!(obj.matches("\nf ").count(), 12);
+    }
+
+    #[test]
+    fn mesh_example() {
+        let cuts = read_cuts(include_str!("../../examples/elumatec.ncw")).unwrap();
+        let (raw, _) = bar(&cuts[0]).unwrap();
+        let machined = solid(&cuts[0]).unwrap();
+
+        assert!(volume(&machined) > 0.0);
+        assert!(volume(&machined) < volume(&raw));
+    }
+}
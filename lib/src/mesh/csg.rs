@@ -0,0 +1,414 @@
+//! Boolean operations on solids, with binary space partitioning trees (from
+//! the csg.js algorithm). Solids are closed sets of convex polygons.
+
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// Tolerance used to decide whether a point is on a plane.
+const EPSILON: f64 = 1e-5;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Vector {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Vector {
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
+        Vector { x, y, z }
+    }
+
+    pub fn dot(self, other: Self) -> f64 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(self, other: Self) -> Self {
+        Vector::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn length(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn unit(self) -> Self {
+        self * (1.0 / self.length())
+    }
+
+    fn lerp(self, other: Self, t: f64) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Add for Vector {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Vector::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl Sub for Vector {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Vector::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl Mul<f64> for Vector {
+    type Output = Self;
+
+    fn mul(self, factor: f64) -> Self {
+        Vector::new(self.x * factor, self.y * factor, self.z * factor)
+    }
+}
+
+impl Neg for Vector {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self * -1.0
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Plane {
+    normal: Vector,
+    w: f64,
+}
+
+impl Plane {
+    fn from_points(a: Vector, b: Vector, c: Vector) -> Self {
+        let normal = (b - a).cross(c - a).unit();
+        Plane {
+            normal,
+            w: normal.dot(a),
+        }
+    }
+
+    fn flip(&mut self) {
+        self.normal = -self.normal;
+        self.w = -self.w;
+    }
+
+    /// Splits `polygon` by this plane. Coplanar polygons go to either
+    /// `coplanar_front` or `coplanar_back` depending on their orientation.
+    fn split(
+        &self,
+        polygon: &Polygon,
+        coplanar_front: &mut Vec<Polygon>,
+        coplanar_back: &mut Vec<Polygon>,
+        front: &mut Vec<Polygon>,
+        back: &mut Vec<Polygon>,
+    ) {
+        const COPLANAR: u8 = 0;
+        const FRONT: u8 = 1;
+        const BACK: u8 = 2;
+        const SPANNING: u8 = 3;
+
+        let types = polygon
+            .vertices
+            .iter()
+            .map(|vertex| {
+                let distance = self.normal.dot(*vertex) - self.w;
+                if distance < -EPSILON {
+                    BACK
+                } else if distance > EPSILON {
+                    FRONT
+                } else {
+                    COPLANAR
+                }
+            })
+            .collect::<Vec<_>>();
+
+        match types.iter().fold(COPLANAR, |kind, vertex| kind | vertex) {
+            COPLANAR => {
+                if self.normal.dot(polygon.plane.normal) > 0.0 {
+                    coplanar_front.push(polygon.clone());
+                } else {
+                    coplanar_back.push(polygon.clone());
+                }
+            }
+
+            FRONT => front.push(polygon.clone()),
+
+            BACK => back.push(polygon.clone()),
+
+            _ => {
+                let (mut front_vertices, mut back_vertices) = (vec![], vec![]);
+                let count = polygon.vertices.len();
+
+                for index in 0..count {
+                    let next = (index + 1) % count;
+                    let (kind, next_kind) = (types[index], types[next]);
+                    let (vertex, next_vertex) = (polygon.vertices[index], polygon.vertices[next]);
+
+                    if kind != BACK {
+                        front_vertices.push(vertex);
+                    }
+                    if kind != FRONT {
+                        back_vertices.push(vertex);
+                    }
+
+                    if kind | next_kind == SPANNING {
+                        let t = (self.w - self.normal.dot(vertex))
+                            / self.normal.dot(next_vertex - vertex);
+                        let intersection = vertex.lerp(next_vertex, t);
+                        front_vertices.push(intersection);
+                        back_vertices.push(intersection);
+                    }
+                }
+
+                if front_vertices.len() >= 3 {
+                    front.push(Polygon::with_plane(front_vertices, polygon.plane));
+                }
+                if back_vertices.len() >= 3 {
+                    back.push(Polygon::with_plane(back_vertices, polygon.plane));
+                }
+            }
+        }
+    }
+}
+
+/// Convex polygon, counterclockwise when seen from the outside of the solid.
+#[derive(Clone, Debug)]
+pub(crate) struct Polygon {
+    pub vertices: Vec<Vector>,
+    plane: Plane,
+}
+
+impl Polygon {
+    pub fn new(vertices: Vec<Vector>) -> Self {
+        let plane = Plane::from_points(vertices[0], vertices[1], vertices[2]);
+        Polygon { vertices, plane }
+    }
+
+    fn with_plane(vertices: Vec<Vector>, plane: Plane) -> Self {
+        Polygon { vertices, plane }
+    }
+
+    pub fn normal(&self) -> Vector {
+        self.plane.normal
+    }
+
+    fn flip(&mut self) {
+        self.vertices.reverse();
+        self.plane.flip();
+    }
+}
+
+#[derive(Default)]
+struct Node {
+    plane: Option<Plane>,
+    front: Option<Box<Node>>,
+    back: Option<Box<Node>>,
+    polygons: Vec<Polygon>,
+}
+
+impl Node {
+    fn new(polygons: Vec<Polygon>) -> Self {
+        let mut node = Node::default();
+        node.build(polygons);
+        node
+    }
+
+    /// Converts solid space to empty space and empty space to solid space.
+    fn invert(&mut self) {
+        for polygon in &mut self.polygons {
+            polygon.flip();
+        }
+        if let Some(plane) = &mut self.plane {
+            plane.flip();
+        }
+        if let Some(front) = &mut self.front {
+            front.invert();
+        }
+        if let Some(back) = &mut self.back {
+            back.invert();
+        }
+        std::mem::swap(&mut self.front, &mut self.back);
+    }
+
+    /// Removes the parts of `polygons` that are inside this tree.
+    fn clip_polygons(&self, polygons: Vec<Polygon>) -> Vec<Polygon> {
+        let Some(plane) = &self.plane else {
+            return polygons;
+        };
+
+        let (mut front, mut back) = (vec![], vec![]);
+        for polygon in &polygons {
+            let (mut coplanar_front, mut coplanar_back) = (vec![], vec![]);
+            plane.split(
+                polygon,
+                &mut coplanar_front,
+                &mut coplanar_back,
+                &mut front,
+                &mut back,
+            );
+            front.append(&mut coplanar_front);
+            back.append(&mut coplanar_back);
+        }
+
+        let mut front = match &self.front {
+            Some(node) => node.clip_polygons(front),
+            None => front,
+        };
+        let back = match &self.back {
+            Some(node) => node.clip_polygons(back),
+            None => vec![],
+        };
+
+        front.extend(back);
+        front
+    }
+
+    /// Removes the parts of this tree's polygons that are inside `other`.
+    fn clip_to(&mut self, other: &Node) {
+        self.polygons = other.clip_polygons(std::mem::take(&mut self.polygons));
+        if let Some(front) = &mut self.front {
+            front.clip_to(other);
+        }
+        if let Some(back) = &mut self.back {
+            back.clip_to(other);
+        }
+    }
+
+    fn all_polygons(&self) -> Vec<Polygon> {
+        let mut polygons = self.polygons.clone();
+        if let Some(front) = &self.front {
+            polygons.extend(front.all_polygons());
+        }
+        if let Some(back) = &self.back {
+            polygons.extend(back.all_polygons());
+        }
+
+        polygons
+    }
+
+    fn build(&mut self, polygons: Vec<Polygon>) {
+        if polygons.is_empty() {
+            return;
+        }
+
+        let plane = *self.plane.get_or_insert(polygons[0].plane);
+        let (mut front, mut back) = (vec![], vec![]);
+        for polygon in &polygons {
+            let (mut coplanar_front, mut coplanar_back) = (vec![], vec![]);
+            plane.split(
+                polygon,
+                &mut coplanar_front,
+                &mut coplanar_back,
+                &mut front,
+                &mut back,
+            );
+            self.polygons.append(&mut coplanar_front);
+            self.polygons.append(&mut coplanar_back);
+        }
+
+        if !front.is_empty() {
+            self.front.get_or_insert_with(Default::default).build(front);
+        }
+        if !back.is_empty() {
+            self.back.get_or_insert_with(Default::default).build(back);
+        }
+    }
+}
+
+/// Solid `a` minus solid `b`.
+pub(crate) fn subtract(a: Vec<Polygon>, b: Vec<Polygon>) -> Vec<Polygon> {
+    let mut a = Node::new(a);
+    let mut b = Node::new(b);
+
+    a.invert();
+    a.clip_to(&b);
+    b.clip_to(&a);
+    b.invert();
+    b.clip_to(&a);
+    b.invert();
+    a.build(b.all_polygons());
+    a.invert();
+
+    a.all_polygons()
+}
+
+/// Right prism of the convex `outline`, extruded by `offset`.
+pub(crate) fn prism(outline: &[Vector], offset: Vector) -> Vec<Polygon> {
+    let mut bottom = outline.to_vec();
+    if Polygon::new(bottom.clone()).normal().dot(offset) > 0.0 {
+        bottom.reverse();
+    }
+
+    let mut top = bottom
+        .iter()
+        .map(|vertex| *vertex + offset)
+        .collect::<Vec<_>>();
+    top.reverse();
+
+    let mut polygons = vec![Polygon::new(bottom.clone()), Polygon::new(top)];
+    for (index, &vertex) in bottom.iter().enumerate() {
+        let next = bottom[(index + 1) % bottom.len()];
+        polygons.push(Polygon::new(vec![
+            vertex,
+            vertex + offset,
+            next + offset,
+            next,
+        ]));
+    }
+
+    polygons
+}
+
+/// Volume enclosed by `polygons`.
+#[cfg(test)]
+pub(crate) fn volume(polygons: &[Polygon]) -> f64 {
+    polygons
+        .iter()
+        .flat_map(|polygon| {
+            (1..polygon.vertices.len() - 1).map(|index| {
+                let (a, b, c) = (
+                    polygon.vertices[0],
+                    polygon.vertices[index],
+                    polygon.vertices[index + 1],
+                );
+                a.dot(b.cross(c)) / 6.0
+            })
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube(origin: Vector, size: f64) -> Vec<Polygon> {
+        let square = [
+            origin,
+            origin + Vector::new(size, 0.0, 0.0),
+            origin + Vector::new(size, size, 0.0),
+            origin + Vector::new(0.0, size, 0.0),
+        ];
+
+        prism(&square, Vector::new(0.0, 0.0, size))
+    }
+
+    #[test]
+    fn prisms_are_closed_and_outward() {
+        let cube = cube(Vector::default(), 2.0);
+        assert_eq!(cube.len(), 6);
+        assert!((volume(&cube) - 8.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn subtract_corner() {
+        let result = subtract(
+            cube(Vector::default(), 2.0),
+            cube(Vector::new(1.0, 1.0, 1.0), 2.0),
+        );
+
+        assert!((volume(&result) - 7.0).abs() < 1e-9);
+    }
+}
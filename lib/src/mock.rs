@@ -0,0 +1,410 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::Url;
+use serde_json::{json, Value};
+
+use crate::cover::Project;
+
+/// Cover API serving the projects of a folder : `projects.json` lists them,
+/// and `<uuid>.json` holds the document data of each one. Uploaded documents
+/// are kept in memory. Stops when dropped.
+pub struct MockServer {
+    address: SocketAddr,
+    requests: Arc<Mutex<Vec<String>>>,
+    uploads: Arc<Mutex<Vec<Upload>>>,
+    failing: Arc<AtomicUsize>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Document posted to `/document`.
+#[derive(Clone, Debug)]
+pub struct Upload {
+    pub project_uuid: String,
+    pub metadata: Value,
+    pub file_name: String,
+    pub contents: Vec<u8>,
+}
+
+/// Request read from a connection.
+struct Request {
+    method: String,
+    target: String,
+    content_type: String,
+    body: Vec<u8>,
+}
+
+/// Field of a `multipart/form-data` body.
+struct Field {
+    name: String,
+    file_name: Option<String>,
+    contents: Vec<u8>,
+}
+
+impl MockServer {
+    /// Serves `folder` on `address`, `127.0.0.1:0` picking any free port.
+    pub fn start(folder: impl Into<PathBuf>, address: &str) -> Result<Self> {
+        let folder = folder.into();
+        let listener =
+            TcpListener::bind(address).with_context(|| format!("Unable to listen on {address}"))?;
+        let address = listener.local_addr()?;
+
+        let requests = Arc::new(Mutex::new(vec![]));
+        let uploads = Arc::new(Mutex::new(vec![]));
+        let failing = Arc::new(AtomicUsize::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let requests = requests.clone();
+            let uploads = uploads.clone();
+            let failing = failing.clone();
+            let stop = stop.clone();
+
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+
+                    let Ok(stream) = stream else {
+                        continue;
+                    };
+                    if let Err(err) = serve(&folder, stream, &requests, &uploads, &failing) {
+                        eprintln!("Mock Cover API : {err:#}");
+                    }
+                }
+            })
+        };
+
+        Ok(Self {
+            address,
+            requests,
+            uploads,
+            failing,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    /// Method and target of every request served, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Documents uploaded, in order.
+    pub fn uploads(&self) -> Vec<Upload> {
+        self.uploads.lock().unwrap().clone()
+    }
+
+    /// Answers the next `count` uploads with `503 Service Unavailable`, after
+    /// storing them, as a server failing once the work is done.
+    pub fn fail_uploads(&self, count: usize) {
+        self.failing.store(count, Ordering::SeqCst);
+    }
+
+    /// Serves requests until the process ends.
+    pub fn wait(mut self) {
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+
+        // Wakes the listener up, so that it sees it must stop
+        if TcpStream::connect(self.address).is_ok() {
+            if let Some(thread) = self.thread.take() {
+                thread.join().ok();
+            }
+        }
+    }
+}
+
+/// Reads a request of `stream` and answers it.
+fn serve(
+    folder: &Path,
+    mut stream: TcpStream,
+    requests: &Mutex<Vec<String>>,
+    uploads: &Mutex<Vec<Upload>>,
+    failing: &AtomicUsize,
+) -> Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut head = String::new();
+    while reader.read_line(&mut head)? > 2 {}
+
+    let header = |key: &str| {
+        head.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case(key)
+                .then(|| value.trim().to_owned())
+        })
+    };
+
+    let length = header("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or_default();
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+    let request = Request {
+        method: request_line.next().unwrap_or_default().to_owned(),
+        target: request_line.next().unwrap_or_default().to_owned(),
+        content_type: header("content-type").unwrap_or_default(),
+        body,
+    };
+    requests
+        .lock()
+        .unwrap()
+        .push(format!("{} {}", request.method, request.target));
+
+    let (status, body) = match respond(folder, &request, uploads) {
+        Ok(Some(_))
+            if request.method == "POST"
+                && failing
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                        count.checked_sub(1)
+                    })
+                    .is_ok() =>
+        {
+            (503, String::new())
+        }
+        Ok(Some(body)) if request.method == "POST" => (201, serde_json::to_string(&body)?),
+        Ok(Some(body)) => (200, serde_json::to_string(&body)?),
+        Ok(None) => (404, String::new()),
+        Err(err) => (500, json!({ "error": format!("{err:#}") }).to_string()),
+    };
+    let reason = match status {
+        200 => "OK",
+        201 => "Created",
+        404 => "Not Found",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+
+    Ok(())
+}
+
+/// Body of the response to `request`, `None` when nothing is found.
+fn respond(
+    folder: &Path,
+    request: &Request,
+    uploads: &Mutex<Vec<Upload>>,
+) -> Result<Option<Value>> {
+    let url = Url::parse(&format!("http://mock{}", request.target))?;
+    let param = |key: &str| {
+        url.query_pairs()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.into_owned())
+    };
+
+    let path = folder.join("projects.json");
+    let projects: Vec<Project> = serde_json::from_str(
+        &fs::read_to_string(&path).with_context(|| format!("Unable to read {}", path.display()))?,
+    )
+    .with_context(|| format!("Invalid project list {}", path.display()))?;
+
+    let find = |matches: &dyn Fn(&Project) -> bool| -> Result<Option<Value>> {
+        Ok(projects
+            .iter()
+            .find(|project| matches(project))
+            .map(serde_json::to_value)
+            .transpose()?)
+    };
+
+    match (request.method.as_str(), url.path()) {
+        ("GET", "/project/search/findById") => {
+            let id = param("id").and_then(|id| id.parse().ok());
+            find(&|project| project.id.is_some() && project.id == id)
+        }
+
+        ("GET", "/project/search/findByUuid") => {
+            let uuid = param("uuid");
+            find(&|project| Some(&project.uuid) == uuid.as_ref())
+        }
+
+        ("GET", "/project/search/findByName") => {
+            let name = param("name").unwrap_or_default().to_lowercase();
+            let found = projects
+                .iter()
+                .filter(|project| project.name.to_lowercase().contains(&name))
+                .collect::<Vec<_>>();
+
+            Ok(Some(serde_json::to_value(found)?))
+        }
+
+        ("GET", "/project") => {
+            let size = param("size")
+                .and_then(|size| size.parse().ok())
+                .unwrap_or(20usize)
+                .max(1);
+            let number = param("page")
+                .and_then(|page| page.parse().ok())
+                .unwrap_or(0usize);
+            let page = projects.chunks(size).nth(number).unwrap_or_default();
+
+            Ok(Some(json!({
+                "_embedded": { "projects": page },
+                "page": {
+                    "size": size,
+                    "totalElements": projects.len(),
+                    "totalPages": projects.len().div_ceil(size),
+                    "number": number,
+                },
+            })))
+        }
+
+        ("GET", "/documentData/search/findProjectDataByProjectUuid") => {
+            // Only listed projects are read, keeping requests inside the folder
+            let uuid = param("projectUuid").unwrap_or_default();
+            if !projects.iter().any(|project| project.uuid == uuid) {
+                return Ok(None);
+            }
+
+            let path = folder.join(format!("{uuid}.json"));
+            if !path.is_file() {
+                return Ok(None);
+            }
+
+            Ok(Some(
+                serde_json::from_str(&fs::read_to_string(&path)?)
+                    .with_context(|| format!("Invalid project data {}", path.display()))?,
+            ))
+        }
+
+        ("POST", "/document") => {
+            let fields = form_fields(&request.content_type, &request.body)?;
+            let field = |name: &str| {
+                fields
+                    .iter()
+                    .find(|field| field.name == name)
+                    .ok_or(anyhow!("No `{name}` field in the upload"))
+            };
+
+            let project_uuid =
+                String::from_utf8_lossy(&field("projectUuid")?.contents).into_owned();
+            if !projects.iter().any(|project| project.uuid == project_uuid) {
+                return Ok(None);
+            }
+
+            let file = field("file")?;
+            let upload = Upload {
+                project_uuid,
+                metadata: serde_json::from_slice(&field("metadata")?.contents)
+                    .context("Invalid upload metadata")?,
+                file_name: file.file_name.clone().unwrap_or_default(),
+                contents: file.contents.clone(),
+            };
+
+            let mut uploads = uploads.lock().unwrap();
+            let document = json!({
+                "id": uploads.len() + 1,
+                "projectUuid": upload.project_uuid,
+                "fileName": upload.file_name,
+                "size": upload.contents.len(),
+                "metadata": upload.metadata,
+            });
+            uploads.push(upload);
+
+            Ok(Some(document))
+        }
+
+        (method, path) => Err(anyhow!("Unsupported request {method} {path}")),
+    }
+}
+
+/// Fields of a `multipart/form-data` body, split on the boundary of
+/// `content_type`.
+fn form_fields(content_type: &str, body: &[u8]) -> Result<Vec<Field>> {
+    let boundary = content_type
+        .split(';')
+        .find_map(|param| param.trim().strip_prefix("boundary="))
+        .ok_or(anyhow!("No multipart boundary in `{content_type}`"))?
+        .trim_matches('"');
+    let delimiter = format!("--{boundary}").into_bytes();
+
+    let mut fields = vec![];
+    // The preamble comes before the first delimiter, `--` after the last one
+    for part in split(body, &delimiter).into_iter().skip(1) {
+        let Some(part) = part.strip_prefix(b"\r\n") else {
+            break;
+        };
+
+        let at = find(part, b"\r\n\r\n").ok_or(anyhow!("Invalid multipart field"))?;
+        let (head, contents) = (&part[..at], &part[at + 4..]);
+        let head = String::from_utf8_lossy(head);
+        let disposition = head
+            .lines()
+            .find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.eq_ignore_ascii_case("content-disposition")
+                    .then_some(value)
+            })
+            .unwrap_or_default();
+        let param = |key: &str| {
+            disposition.split(';').find_map(|param| {
+                Some(
+                    param
+                        .trim()
+                        .strip_prefix(key)?
+                        .strip_prefix('=')?
+                        .trim_matches('"')
+                        .to_owned(),
+                )
+            })
+        };
+
+        fields.push(Field {
+            name: param("name").unwrap_or_default(),
+            file_name: param("filename"),
+            contents: contents.strip_suffix(b"\r\n").unwrap_or(contents).to_vec(),
+        });
+    }
+
+    Ok(fields)
+}
+
+/// Parts of `bytes` between occurrences of `delimiter`.
+fn split<'a>(bytes: &'a [u8], delimiter: &[u8]) -> Vec<&'a [u8]> {
+    let mut parts = vec![];
+    let mut rest = bytes;
+    while let Some(at) = find(rest, delimiter) {
+        parts.push(&rest[..at]);
+        rest = &rest[at + delimiter.len()..];
+    }
+    parts.push(rest);
+
+    parts
+}
+
+/// Position of the first occurrence of `needle` in `bytes`.
+fn find(bytes: &[u8], needle: &[u8]) -> Option<usize> {
+    bytes
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
@@ -0,0 +1,450 @@
+use std::fs;
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{cover::ProjectData, Source, Split};
+
+/// References of a part within its project.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct PartRefs {
+    pub view: String,
+    pub profile: String,
+    pub bar: String,
+}
+
+/// Saw angles of one end of a part, in degrees.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Extremity {
+    pub h: f32,
+    pub v: f32,
+
+    /// Offset of the cut along the bar, when known.
+    pub z: Option<f32>,
+}
+
+/// Cross-section of a profile, in millimeters.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Section {
+    /// Section family, as DSTV codes : `I`, `U`, `L`, `M` (rectangular tube), `RO`...
+    pub code: String,
+    pub name: String,
+    pub height: f32,
+    pub width: f32,
+    pub flange_thickness: f32,
+    pub web_thickness: f32,
+    pub radius: f32,
+
+    /// Weight in kg/m.
+    pub weight: f32,
+    pub material: String,
+}
+
+/// Face of the profile a machining is done on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Face {
+    /// Web, facing the operator.
+    #[default]
+    Front,
+    Top,
+    Bottom,
+    Back,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MachiningKind {
+    #[default]
+    Drilling,
+    Slot,
+    Contour,
+}
+
+/// Machining of a part. Positions are taken on `face`, `x` along the part
+/// from its left end and `y` across the face from its lower edge.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Machining {
+    pub kind: MachiningKind,
+    pub face: Face,
+    pub x: f32,
+    pub y: f32,
+    pub diameter: f32,
+
+    /// Through machining when not set.
+    pub depth: Option<f32>,
+
+    /// Length of a slot, along the part.
+    pub length: f32,
+
+    /// Contour vertices, relative to (`x`, `y`).
+    pub points: Vec<(f32, f32)>,
+    pub tool: String,
+}
+
+/// A single profile cut, shared by every exporter.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Part {
+    pub refs: PartRefs,
+    pub length: f32,
+    pub left: Extremity,
+    pub right: Extremity,
+    pub quantity: u32,
+
+    /// Position of the part in the project, starting at 1.
+    pub number: usize,
+    pub comment: String,
+    pub section: Section,
+    pub machinings: Vec<Machining>,
+}
+
+impl Part {
+    pub fn from_source(source: &Source) -> Result<Vec<Self>> {
+        match source {
+            Source::Api { data, .. } => Self::from_project_data(data),
+
+            Source::File(path) => {
+                let contents = fs::read_to_string(path)?;
+                Self::from_articles(&serde_json::from_str(&contents)?)
+            }
+        }
+    }
+
+    /// Reads cuts of every structure view, labelled with their view reference.
+    pub fn from_project_data(data: &ProjectData) -> Result<Vec<Self>> {
+        let structure_views = data
+            .structure_views
+            .as_ref()
+            .ok_or(anyhow!("Unable to update cuts, `structureViews` is null"))?;
+        let mut parts = vec![];
+
+        for (view_index, structure_view) in structure_views.iter().enumerate() {
+            let profiles = &structure_view["nomenclature"]["profiles"];
+            if profiles.is_null() {
+                continue;
+            }
+
+            let view = reference(structure_view, &["reference", "name"])
+                .unwrap_or_else(|| format!("V{}", view_index + 1));
+
+            let profiles = profiles.as_array().unwrap();
+            for profile in profiles {
+                let refs = PartRefs {
+                    view: view.clone(),
+                    profile: reference(profile, &["reference"])
+                        .or_else(|| reference(&profile["element"], &["reference"]))
+                        .unwrap_or_default(),
+                    bar: reference(profile, &["bar"]).unwrap_or_default(),
+                };
+
+                let length = profile["length"].as_f64().unwrap() as f32;
+
+                let left = profile["extremity1"]["cuts"].as_array().unwrap();
+                let right = profile["extremity2"]["cuts"].as_array().unwrap();
+
+                for (left, right) in left.iter().zip(right) {
+                    parts.push(Part {
+                        refs: refs.clone(),
+                        length,
+                        left: extremity(left),
+                        right: extremity(right),
+                        quantity: quantity(profile),
+                        number: parts.len() + 1,
+                        comment: view.clone(),
+                        section: section(&profile["section"])
+                            .or_else(|| section(&profile["element"]["section"]))
+                            .unwrap_or_default(),
+                        machinings: machinings(&profile["element"]["machinings"]),
+                    });
+                }
+            }
+        }
+
+        if parts.is_empty() {
+            return Err(anyhow!("Unable to update cuts, no profile found"));
+        }
+
+        Ok(parts)
+    }
+
+    /// Reads cuts of the `profile` articles of a Cover export file.
+    pub fn from_articles(json: &Value) -> Result<Vec<Self>> {
+        let mut parts = vec![];
+
+        let articles = json["articles"]
+            .as_array()
+            .ok_or(anyhow!("Unable to read cuts, `articles` is not set"))?;
+        for article in articles {
+            match article["type"].as_str().unwrap() {
+                "profile" => {
+                    let refs = PartRefs {
+                        view: String::new(),
+                        profile: reference(article, &["reference"]).unwrap_or_default(),
+                        bar: reference(article, &["bar"]).unwrap_or_default(),
+                    };
+
+                    let length = number(&article["length"]).unwrap() as f32;
+
+                    let cuts = article["cuts"].as_array().unwrap();
+                    let left = cuts[0].as_array().unwrap();
+                    let right = cuts[1].as_array().unwrap();
+
+                    for (left, right) in left.iter().zip(right) {
+                        parts.push(Part {
+                            refs: refs.clone(),
+                            length,
+                            left: extremity(left),
+                            right: extremity(right),
+                            quantity: quantity(article),
+                            number: parts.len() + 1,
+                            comment: reference(article, &["comment"]).unwrap_or_default(),
+                            section: section(&article["section"]).unwrap_or_default(),
+                            machinings: machinings(&article["machinings"]),
+                        });
+                    }
+                }
+
+                _ => {
+                    // Other articles are only listed in the bill of materials
+                }
+            }
+        }
+
+        Ok(parts)
+    }
+
+    /// Groups parts by the references `split` keeps, in order of appearance.
+    /// A single group is always returned when not splitting.
+    pub fn group(parts: Vec<Self>, split: Split) -> Result<Vec<(PartRefs, Vec<Self>)>> {
+        let mut groups: Vec<(PartRefs, Vec<Self>)> = vec![];
+        if split == Split::None {
+            groups.push((PartRefs::default(), vec![]));
+        }
+
+        for part in parts {
+            let key = split.key(&part.refs)?;
+            match groups.iter_mut().find(|(refs, _)| *refs == key) {
+                Some((_, parts)) => parts.push(part),
+                None => groups.push((key, vec![part])),
+            }
+        }
+
+        Ok(groups)
+    }
+}
+
+fn extremity(cut: &Value) -> Extremity {
+    Extremity {
+        h: number(&cut["h"]).unwrap() as f32,
+        v: number(&cut["v"]).unwrap() as f32,
+        z: number(&cut["z"]).map(|z| z as f32),
+    }
+}
+
+fn section(section: &Value) -> Option<Section> {
+    if !section.is_object() {
+        return None;
+    }
+
+    let dimension = |key: &str| number(&section[key]).unwrap_or_default() as f32;
+
+    Some(Section {
+        code: reference(section, &["code", "type"]).unwrap_or_default(),
+        name: reference(section, &["name", "reference"]).unwrap_or_default(),
+        height: dimension("height"),
+        width: dimension("width"),
+        flange_thickness: dimension("flangeThickness"),
+        web_thickness: dimension("webThickness"),
+        radius: dimension("radius"),
+        weight: dimension("weight"),
+        material: reference(section, &["material", "grade"]).unwrap_or_default(),
+    })
+}
+
+/// Reads positioned machinings, the ones only made of macro variables are skipped.
+fn machinings(machinings: &Value) -> Vec<Machining> {
+    let Some(machinings) = machinings.as_array() else {
+        return vec![];
+    };
+
+    machinings
+        .iter()
+        .filter_map(|machining| {
+            let x = number(&machining["x"])? as f32;
+            let dimension = |key: &str| number(&machining[key]).unwrap_or_default() as f32;
+
+            let kind = match machining["type"].as_str().unwrap_or_default() {
+                "slot" => MachiningKind::Slot,
+                "contour" => MachiningKind::Contour,
+                _ => MachiningKind::Drilling,
+            };
+
+            let face = match machining["face"].as_str().unwrap_or_default() {
+                "top" => Face::Top,
+                "bottom" => Face::Bottom,
+                "back" => Face::Back,
+                _ => Face::Front,
+            };
+
+            let points = machining["points"]
+                .as_array()
+                .map(|points| {
+                    points
+                        .iter()
+                        .filter_map(|point| {
+                            Some((point[0].as_f64()? as f32, point[1].as_f64()? as f32))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            Some(Machining {
+                kind,
+                face,
+                x,
+                y: dimension("y"),
+                diameter: dimension("diameter"),
+                depth: number(&machining["depth"]).map(|depth| depth as f32),
+                length: dimension("length"),
+                points,
+                tool: reference(machining, &["tool"]).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+fn quantity(article: &Value) -> u32 {
+    number(&article["quantity"]).map_or(1, |quantity| quantity as u32)
+}
+
+/// Reads a number, either plain or wrapped in a `{ "value": ... }` object.
+pub(crate) fn number(value: &Value) -> Option<f64> {
+    value.as_f64().or_else(|| value["value"].as_f64())
+}
+
+/// First of `keys` holding a non-empty string or a number in `value`.
+fn reference(value: &Value, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|key| match &value[key] {
+        Value::String(reference) if !reference.is_empty() => Some(reference.to_owned()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn read_cuts_of_every_view() {
+        let profile = json!({
+            "reference": "L100",
+            "bar": 2,
+            "length": 1000.0,
+            "extremity1": { "cuts": [{ "h": 45.0, "v": 90.0, "z": 0.0 }] },
+            "extremity2": { "cuts": [{ "h": 90.0, "v": 90.0, "z": 0.0 }] },
+        });
+        let data: ProjectData = serde_json::from_value(json!({
+            "structureViews": [
+                { "reference": "F1", "nomenclature": { "profiles": [profile, profile] } },
+                { "nomenclature": null },
+                { "nomenclature": { "profiles": [profile] } },
+            ]
+        }))
+        .unwrap();
+
+        let parts = Part::from_project_data(&data).unwrap();
+        assert_eq!(parts.len(), 3);
+
+        let part = &parts[0];
+        assert_eq!(
+            part.refs,
+            PartRefs {
+                view: "F1".to_owned(),
+                profile: "L100".to_owned(),
+                bar: "2".to_owned(),
+            }
+        );
+        assert_eq!(part.left.h, 45.0);
+        assert_eq!(part.comment, "F1");
+
+        assert_eq!(parts[2].refs.view, "V3");
+        assert_eq!(parts[2].number, 3);
+    }
+
+    #[test]
+    fn read_profile_articles() {
+        let json = json!({
+            "articles": [
+                {
+                    "type": "profile",
+                    "reference": "L100",
+                    "quantity": 2,
+                    "length": { "value": 500 },
+                    "section": { "code": "M", "height": 60, "width": 40 },
+                    "machinings": [
+                        { "type": "slot", "face": "top", "x": 100, "y": 20, "diameter": 8, "length": 30 },
+                        { "operations": [] }
+                    ],
+                    "cuts": [
+                        [{ "h": { "value": 45 }, "v": { "value": 90 } }],
+                        [{ "h": { "value": 90 }, "v": { "value": 90 } }]
+                    ]
+                },
+                { "type": "glass", "reference": "G4" }
+            ]
+        });
+
+        let parts = Part::from_articles(&json).unwrap();
+        assert_eq!(parts.len(), 1);
+        assert!(Part::from_articles(&json!({ "lines": [] })).is_err());
+        assert_eq!(parts[0].length, 500.0);
+        assert_eq!(parts[0].quantity, 2);
+        assert_eq!(parts[0].left.z, None);
+
+        let machining = &parts[0].machinings[0];
+        assert_eq!(machining.kind, MachiningKind::Slot);
+        assert_eq!(machining.face, Face::Top);
+        assert_eq!(machining.depth, None);
+        assert_eq!(parts[0].section.code, "M");
+    }
+
+    #[test]
+    fn group_parts() {
+        let part = |profile: &str, bar: &str| Part {
+            refs: PartRefs {
+                view: "F1".to_owned(),
+                profile: profile.to_owned(),
+                bar: bar.to_owned(),
+            },
+            length: 0.0,
+            left: Extremity::default(),
+            right: Extremity::default(),
+            quantity: 1,
+            number: 1,
+            ..Default::default()
+        };
+        let parts = || vec![part("L100", "1"), part("L200", "1"), part("L100", "2")];
+
+        let groups = Part::group(parts(), Split::None).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].1.len(), 3);
+
+        let groups = Part::group(parts(), Split::Profile).unwrap();
+        let profiles = groups
+            .iter()
+            .map(|(refs, _)| refs.profile.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(profiles, ["L100", "L200"]);
+        assert_eq!(groups[0].1.len(), 2);
+
+        let groups = Part::group(parts(), Split::Bar).unwrap();
+        assert_eq!(groups.len(), 3);
+
+        let unknown = vec![part("L100", "1"), part("L200", "")];
+        assert!(Part::group(unknown.clone(), Split::Bar).is_err());
+        assert_eq!(Part::group(unknown, Split::Profile).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,275 @@
+use anyhow::{anyhow, Result};
+
+use crate::{
+    clamp,
+    config::Templates,
+    document::{documents, read_cuts, Bar, Cut},
+    exporter::write_jobs,
+    part::Face,
+    ExportOptions,
+};
+
+/// Width of the drawing, in pixels.
+const WIDTH: f32 = 960.0;
+
+/// Size of the section drawn next to each cut, in pixels.
+const SECTION_SIZE: f32 = 120.0;
+
+const MARGIN: f32 = 12.0;
+const LABEL_HEIGHT: f32 = 18.0;
+
+/// Gap between the views of a cut, in millimeters.
+const VIEW_GAP: f32 = 40.0;
+
+const STYLE: &str = "path, polygon, circle, line, rect { vector-effect: non-scaling-stroke; }
+.section { fill: #c9d6e3; stroke: #1f3b57; fill-rule: evenodd; }
+.cut { fill: #e8eef4; stroke: #1f3b57; }
+.work { fill: #d9534f; fill-opacity: 0.6; stroke: #a02622; }
+.saw { stroke: #a02622; stroke-dasharray: 4 3; }
+text { font: 12px sans-serif; fill: #1f3b57; }";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Outline of the section, bulges being drawn as arcs. The y axis points
+/// upward in Elumatec coordinates, it is flipped.
+fn section_path(polylines: &[Vec<(f32, f32, f32)>]) -> String {
+    let mut path = String::new();
+
+    for polyline in polylines {
+        let Some(&(x, y, _)) = polyline.first() else {
+            continue;
+        };
+        path += &format!("M{x:.3},{:.3}", -y);
+
+        for (index, &(x0, y0, bulge)) in polyline.iter().enumerate() {
+            let (x1, y1, _) = polyline[(index + 1) % polyline.len()];
+            let chord = (x1 - x0).hypot(y1 - y0);
+            if chord < 1e-6 {
+                continue;
+            }
+
+            if bulge.abs() < 1e-6 {
+                path += &format!(" L{x1:.3},{:.3}", -y1);
+            } else {
+                // Positive bulges turn counterclockwise, SVG sweeps clockwise
+                let radius = chord * (1.0 + bulge * bulge) / (4.0 * bulge.abs());
+                let large_arc = (bulge.abs() > 1.0) as u8;
+                let sweep = (bulge < 0.0) as u8;
+                path += &format!(
+                    " A{radius:.3},{radius:.3} 0 {large_arc} {sweep} {x1:.3},{:.3}",
+                    -y1
+                );
+            }
+        }
+
+        path += " Z ";
+    }
+
+    path.trim_end().to_owned()
+}
+
+fn draw_section(bar: &Bar, y: f32) -> String {
+    let (shape, (min_x, min_y, max_x, max_y)) = if bar.polylines.is_empty() {
+        if bar.width <= 0.0 || bar.height <= 0.0 {
+            return String::new();
+        }
+
+        (
+            format!(
+                "<rect class=\"section\" x=\"0\" y=\"{:.3}\" width=\"{:.3}\" height=\"{:.3}\"/>",
+                -bar.height, bar.width, bar.height
+            ),
+            (0.0, -bar.height, bar.width, 0.0),
+        )
+    } else {
+        let bounds = bar.polylines.iter().flatten().fold(
+            (f32::MAX, f32::MAX, f32::MIN, f32::MIN),
+            |(min_x, min_y, max_x, max_y), &(x, y, _)| {
+                (min_x.min(x), min_y.min(-y), max_x.max(x), max_y.max(-y))
+            },
+        );
+
+        (
+            format!(
+                "<path class=\"section\" d=\"{}\"/>",
+                section_path(&bar.polylines)
+            ),
+            bounds,
+        )
+    };
+
+    let padding = (max_x - min_x).max(max_y - min_y) * 0.05;
+    format!(
+        "<svg x=\"{MARGIN}\" y=\"{y:.1}\" width=\"{SECTION_SIZE}\" height=\"{SECTION_SIZE}\" viewBox=\"{:.3} {:.3} {:.3} {:.3}\">{shape}</svg>\n",
+        min_x - padding,
+        min_y - padding,
+        max_x - min_x + padding * 2.0,
+        max_y - min_y + padding * 2.0,
+    )
+}
+
+/// Views of the cut : front and top, back and bottom only when machined.
+/// Returns the drawing and its height in pixels.
+fn draw_cut(cut: &Cut, y: f32) -> (String, f32) {
+    let mut views = vec![
+        (Face::Front, cut.left.0, cut.right.0),
+        (Face::Top, cut.left.1, cut.right.1),
+    ];
+    for face in [Face::Bottom, Face::Back] {
+        if cut.works.iter().any(|work| work.face == face) {
+            let angles = if face == Face::Back {
+                (cut.left.0, cut.right.0)
+            } else {
+                (cut.left.1, cut.right.1)
+            };
+            views.push((face, angles.0, angles.1));
+        }
+    }
+
+    let length = cut.length.max(1.0);
+    let padding = length * 0.02;
+    let marker = length / 150.0;
+
+    let mut shapes = String::new();
+    let mut origin_y = 0.0;
+    for (face, left, right) in views {
+        let size = cut.bar.size(face);
+        let offset = |angle: f32| size / angle.to_radians().tan();
+        let (left_offset, right_offset) = (offset(left), offset(right));
+
+        shapes += &format!(
+            "<polygon class=\"cut\" points=\"0,{:.3} {length:.3},{:.3} {:.3},{origin_y:.3} {left_offset:.3},{origin_y:.3}\"><title>{face:?}</title></polygon>\n",
+            origin_y + size,
+            origin_y + size,
+            length - right_offset,
+        );
+
+        for work in cut.works.iter().filter(|work| work.face == face) {
+            let title = escape(&format!("{} {} {}", work.kind, work.number, work.comment));
+
+            if work.kind == "S" {
+                shapes += &format!(
+                    "<line class=\"saw\" x1=\"{:.3}\" y1=\"{origin_y:.3}\" x2=\"{:.3}\" y2=\"{:.3}\"><title>{title}</title></line>\n",
+                    work.x,
+                    work.x,
+                    origin_y + size
+                );
+            } else {
+                shapes += &format!(
+                    "<circle class=\"work\" cx=\"{:.3}\" cy=\"{:.3}\" r=\"{:.3}\"><title>{title}</title></circle>\n",
+                    work.x,
+                    origin_y + work.y,
+                    (work.diameter / 2.0).max(marker)
+                );
+            }
+        }
+
+        origin_y += size + VIEW_GAP;
+    }
+
+    let views_height = origin_y - VIEW_GAP + padding * 2.0;
+    let width = WIDTH - SECTION_SIZE - MARGIN * 3.0;
+    let height = views_height * width / (length + padding * 2.0);
+
+    (
+        format!(
+            "<svg x=\"{:.1}\" y=\"{y:.1}\" width=\"{width:.1}\" height=\"{height:.1}\" viewBox=\"{:.3} {:.3} {:.3} {views_height:.3}\">\n{shapes}</svg>\n",
+            SECTION_SIZE + MARGIN * 2.0,
+            -padding,
+            -padding,
+            length + padding * 2.0,
+        ),
+        height,
+    )
+}
+
+/// Renders an Elumatec document to SVG : one row per cut, with the bar
+/// section and the views of the part, its works placed on their face.
+pub fn render(document: &str) -> Result<String> {
+    let mut rows = String::new();
+    let mut y = MARGIN;
+
+    let cuts = read_cuts(document)?;
+    if cuts.is_empty() {
+        return Err(anyhow!("Nothing to preview : the document has no `:CUT`"));
+    }
+
+    for cut in cuts {
+        rows += &format!(
+            "<text x=\"{MARGIN}\" y=\"{:.1}\">{}</text>\n",
+            y + 13.0,
+            escape(&format!(
+                "Cut {} - part {} - {} mm - left {}°/{}° - right {}°/{}°",
+                cut.number, cut.part, cut.length, cut.left.0, cut.left.1, cut.right.0, cut.right.1
+            ))
+        );
+        y += LABEL_HEIGHT;
+
+        rows += &draw_section(&cut.bar, y);
+        let (views, height) = draw_cut(&cut, y);
+        rows += &views;
+
+        y += height.max(SECTION_SIZE) + MARGIN;
+    }
+
+    Ok(format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{y:.1}\" viewBox=\"0 0 {WIDTH} {y:.1}\">\n<style>\n{STYLE}\n</style>\n{rows}</svg>\n"
+    ))
+}
+
+/// Renders an Elumatec document, or the jobs generated from a JSON source,
+/// without reaching the API.
+pub fn preview(
+    source: &str,
+    output_path: Option<String>,
+    options: &ExportOptions,
+    templates: &Templates,
+) -> Result<()> {
+    let documents = documents(source, &output_path, options, templates)?;
+    clamp::verify(&documents)?;
+
+    let drawings = documents
+        .into_iter()
+        .map(|(name, document)| Ok((name, render(&document)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    write_jobs(drawings, &output_path, "svg")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("../../examples/elumatec.ncw");
+
+    #[test]
+    fn draw_bulges_as_arcs() {
+        let polylines = vec![vec![
+            (0.0, 98.5, -0.414214),
+            (1.5, 100.0, 0.0),
+            (60.0, 100.0, 0.0),
+        ]];
+
+        assert_eq!(
+            section_path(&polylines),
+            "M0.000,-98.500 A1.500,1.500 0 0 1 1.500,-100.000 L60.000,-100.000 L0.000,-98.500 Z"
+        );
+    }
+
+    #[test]
+    fn render_every_cut() {
+        let svg = render(EXAMPLE).unwrap();
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert_eq!(svg.matches("class=\"section\"").count(), 1);
+        assert!(svg.contains("right 78.47212°/90°"));
+        assert!(render(":OPTIONS\nOScale = 1\n").is_err());
+    }
+}
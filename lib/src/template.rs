@@ -0,0 +1,366 @@
+use std::{fmt::Display, fs, path::PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::user_dir,
+    exporter::elumatec::{read_tags, tag::Tag},
+    util::hex,
+};
+
+mod engine;
+
+/// Templates compiled into the binary, used when no folder holds them.
+const BUILTIN: [(&str, &str); 3] = [
+    ("elumatec", include_str!("../../templates/elumatec")),
+    ("gcode", include_str!("../../templates/gcode")),
+    ("machines", include_str!("../../templates/machines")),
+];
+
+/// Template folders of the configuration.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Templates {
+    /// Folders searched first, relative ones from the file declaring them.
+    pub paths: Vec<PathBuf>,
+}
+
+/// Where a template was read from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Origin {
+    File(PathBuf),
+    Builtin,
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Builtin => write!(f, "built-in"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Template {
+    pub name: String,
+    pub origin: Origin,
+    pub contents: String,
+    /// Templates of the `include` directives.
+    pub includes: Vec<Template>,
+    /// Template of the `extends` directive.
+    pub base: Option<Box<Template>>,
+}
+
+impl Templates {
+    /// Folders searched in order : the configured ones, then the `templates`
+    /// folder of the project (working) directory and of the user configuration
+    /// directory.
+    pub fn search_path(&self) -> Vec<PathBuf> {
+        let mut folders = self.paths.clone();
+        folders.push(PathBuf::from("templates"));
+        folders.extend(user_dir().map(|folder| folder.join("templates")));
+
+        folders
+    }
+
+    /// Reads the template `name` from the first folder holding it, or from
+    /// the built-in templates. The template used is reported on stderr.
+    pub fn load(&self, name: &str) -> Result<Template> {
+        let template = self.resolve(name, &mut vec![])?;
+        eprintln!("Using `{name}` template : {}", template.origin);
+
+        Ok(template)
+    }
+
+    /// Finds the template `name` and the templates it includes or extends,
+    /// `chain` holding the templates being resolved.
+    fn resolve(&self, name: &str, chain: &mut Vec<String>) -> Result<Template> {
+        let mut template = Self::find(&self.search_path(), name)?;
+        let invalid = || format!("Invalid template {}", template.origin);
+
+        let lines = template.contents.split('\n').collect::<Vec<_>>();
+        let dependencies = engine::dependencies(&template.contents).with_context(invalid)?;
+
+        chain.push(name.to_owned());
+        let mut includes: Vec<Template> = vec![];
+        let mut base = None;
+
+        for dependency in dependencies {
+            let fail = |message: String| {
+                Err(engine::error(&lines, dependency.line, message)).with_context(invalid)
+            };
+
+            if chain.contains(&dependency.name) {
+                return fail(format!(
+                    "Include cycle {} -> {}",
+                    chain.join(" -> "),
+                    dependency.name
+                ));
+            }
+
+            if !dependency.extends && includes.iter().any(|i| i.name == dependency.name) {
+                continue;
+            }
+
+            let resolved = self
+                .resolve(&dependency.name, chain)
+                .map_err(|err| {
+                    err.context(engine::error(
+                        &lines,
+                        dependency.line,
+                        format!("Unable to load template `{}`", dependency.name),
+                    ))
+                })
+                .with_context(invalid)?;
+
+            if dependency.extends {
+                base = Some(Box::new(resolved));
+            } else if resolved.base.is_some() {
+                return fail(format!(
+                    "Included template `{}` extends another template",
+                    dependency.name
+                ));
+            } else {
+                includes.push(resolved);
+            }
+        }
+        chain.pop();
+
+        template.includes = includes;
+        template.base = base;
+
+        Ok(template)
+    }
+
+    fn find(folders: &[PathBuf], name: &str) -> Result<Template> {
+        if let Some(path) = folders
+            .iter()
+            .map(|folder| folder.join(name))
+            .find(|path| path.is_file())
+        {
+            return Ok(Template {
+                name: name.to_owned(),
+                contents: fs::read_to_string(&path)
+                    .with_context(|| format!("Unable to read template {}", path.display()))?,
+                origin: Origin::File(path),
+                includes: vec![],
+                base: None,
+            });
+        }
+
+        let (_, contents) = BUILTIN
+            .into_iter()
+            .find(|(builtin, _)| *builtin == name)
+            .ok_or(anyhow!(
+                "Template `{name}` not found in {}",
+                folders
+                    .iter()
+                    .map(|folder| folder.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))?;
+
+        Ok(Template {
+            name: name.to_owned(),
+            origin: Origin::Builtin,
+            contents: contents.to_owned(),
+            includes: vec![],
+            base: None,
+        })
+    }
+}
+
+impl Template {
+    /// Renders the placeholders, conditionals and loops of the template with
+    /// the values of `context`.
+    pub fn render(&self, context: &Value) -> Result<String> {
+        engine::render(self, context).with_context(|| format!("Invalid template {}", self.origin))
+    }
+
+    /// SHA-256 of the contents of the template and of those it includes or
+    /// extends, as lowercase hexadecimal : any change of the rendered output
+    /// changes it.
+    pub fn hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        self.digest(&mut hasher);
+
+        hex(&hasher.finalize())
+    }
+
+    fn digest(&self, hasher: &mut Sha256) {
+        // Lengths keep the boundaries between templates unambiguous
+        for part in [&self.name, &self.contents] {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+
+        for include in &self.includes {
+            include.digest(hasher);
+        }
+        if let Some(base) = &self.base {
+            base.digest(hasher);
+        }
+    }
+
+    /// Tags of the template rendered with `context`, which must hold every
+    /// tag of `required`. Tags of an extending template override those of
+    /// its base : the attributes of the n-th tag of a name are set on the
+    /// n-th tag of that name of the base, tags without counterpart are added.
+    pub(crate) fn tags(&self, context: &Value, required: &[&str]) -> Result<Vec<Tag>> {
+        let mut tags = read_tags(&self.render(context)?)
+            .with_context(|| format!("Invalid template {}", self.origin))?;
+
+        if let Some(base) = &self.base {
+            let mut inherited = base.tags(context, &[])?;
+            let mut seen: Vec<&str> = vec![];
+
+            for tag in &tags {
+                let occurrence = seen.iter().filter(|name| **name == tag.name).count();
+                seen.push(&tag.name);
+
+                match inherited
+                    .iter_mut()
+                    .filter(|other| other.name == tag.name)
+                    .nth(occurrence)
+                {
+                    Some(other) => other.merge(tag.clone()),
+                    None => inherited.push(tag.clone()),
+                }
+            }
+
+            tags = inherited;
+        }
+
+        let missing = required
+            .iter()
+            .filter(|name| !tags.iter().any(|tag| tag.name == **name))
+            .map(|name| format!("`:{name}`"))
+            .collect::<Vec<_>>();
+
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "Template `{}` ({}) lacks the required {} tag{}",
+                self.name,
+                self.origin,
+                missing.join(", "),
+                if missing.len() > 1 { "s" } else { "" }
+            ));
+        }
+
+        Ok(tags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exporter::elumatec::variant::Variant;
+
+    #[test]
+    fn fall_back_to_builtin() {
+        let temp = tempfile::tempdir().unwrap();
+        let folder = temp.path();
+        fs::write(folder.join("elumatec"), ":OPTIONS\nOScale = 1\n").unwrap();
+
+        let folders = [PathBuf::from("missing"), folder.to_path_buf()];
+
+        let template = Templates::find(&folders, "elumatec").unwrap();
+        assert_eq!(template.origin, Origin::File(folder.join("elumatec")));
+
+        let Err(err) = template.tags(&Value::Null, &["OPTIONS", "JOB"]) else {
+            panic!("`:JOB` is missing");
+        };
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Template `elumatec` ({}) lacks the required `:JOB` tag",
+                folder.join("elumatec").display()
+            )
+        );
+
+        let template = Templates::find(&folders, "gcode").unwrap();
+        assert_eq!(template.origin, Origin::Builtin);
+        assert!(template.tags(&Value::Null, &["POST"]).is_ok());
+
+        assert!(Templates::find(&folders, "unknown").is_err());
+    }
+
+    #[test]
+    fn include_and_extend_templates() {
+        let temp = tempfile::tempdir().unwrap();
+        let folder = temp.path();
+        let write = |name: &str, contents: &str| fs::write(folder.join(name), contents).unwrap();
+
+        write(
+            "base",
+            ":OPTIONS\nOScale = 1\nOSecClampDistX = 5\n{% include \"job\" %}\n:TOOL\nId = \"D8\"\n",
+        );
+        write("job", ":JOB\ninfo = \"{{ project }}\"");
+        write(
+            "machine",
+            "// Safety distances only\n{% extends \"base\" %}\n:OPTIONS\nOSecClampDistX = 10\n:TOOL\nId = \"D8\"\n:TOOL\nId = \"FR5K\"\n",
+        );
+
+        let templates = Templates {
+            paths: vec![folder.to_path_buf()],
+        };
+        let tags = templates
+            .load("machine")
+            .unwrap()
+            .tags(&serde_json::json!({ "project": "P1" }), &["OPTIONS", "JOB"])
+            .unwrap();
+
+        let names = tags.iter().map(|tag| tag.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["OPTIONS", "JOB", "TOOL", "TOOL"]);
+        assert_eq!(tags[0].get("OScale"), Some(Variant::Int(1)));
+        assert_eq!(tags[0].get("OSecClampDistX"), Some(Variant::Int(10)));
+        assert_eq!(tags[1].get("info"), Some(Variant::String("P1".to_owned())));
+        assert_eq!(tags[3].get("Id"), Some(Variant::String("FR5K".to_owned())));
+
+        // Cycles and missing templates point at the directive
+        write("job", ":JOB\n\n{% include \"base\" %}");
+        let Err(err) = templates.load("machine") else {
+            panic!("`base` and `job` include each other");
+        };
+        assert!(format!("{err:#}").contains(
+            "{% include \"base\" %}\n^ Include cycle machine -> base -> job -> base on line 3"
+        ));
+
+        write("job", ":JOB\n{% include \"missing\" %}");
+        let Err(err) = templates.load("machine") else {
+            panic!("`missing` does not exist");
+        };
+        let message = format!("{err:#}");
+        assert!(
+            message.contains("{% extends \"base\" %}\n^ Unable to load template `base` on line 2")
+        );
+        assert!(message
+            .contains("{% include \"missing\" %}\n^ Unable to load template `missing` on line 2"));
+        assert!(message.contains("Template `missing` not found in"));
+
+        // Hashes cover the included templates
+        write("job", ":JOB\ninfo = \"{{ project }}\"");
+        let hash = templates.load("machine").unwrap().hash();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(templates.load("machine").unwrap().hash(), hash);
+        write("job", ":JOB\ninfo = \"{{ job }}\"");
+        assert_ne!(templates.load("machine").unwrap().hash(), hash);
+    }
+
+    #[test]
+    fn builtin_templates_are_complete() {
+        for (name, required) in [
+            ("elumatec", &["OPTIONS", "JOB"][..]),
+            ("gcode", &["POST"]),
+            ("machines", &["MACHINE"]),
+        ] {
+            let template = Templates::find(&[], name).unwrap();
+            assert_eq!(template.origin, Origin::Builtin);
+            template.tags(&Value::Null, required).unwrap();
+        }
+    }
+}
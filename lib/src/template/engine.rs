@@ -0,0 +1,888 @@
+//! Line based template language. `{{ expression }}` placeholders are replaced
+//! anywhere on a line, `{% ... %}` lines drive the output :
+//!
+//! ```text
+//! {% for part in parts %}
+//! :CUT
+//! CNo     = {{ loop.index }}
+//! CLength = {{ part.length }}
+//! {% if part.left.h != 90 %}
+//! CAngleLH = {{ part.left.h }}
+//! {% endif %}
+//! {% endfor %}
+//! ```
+//!
+//! Expressions read the context with `name.field` and `list[index]`, and
+//! support literals, `+ - * / %`, comparisons, `and`, `or`, `not` and the
+//! `len`, `round`, `abs`, `min` and `max` functions.
+//!
+//! `{% include "name" %}` renders another template in place, with the same
+//! variables. `{% extends "name" %}` makes the tags of the template override
+//! those of `name`, see [`super::Template::tags`].
+
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+
+use super::Template;
+
+enum Node {
+    Text {
+        line: usize,
+        text: String,
+    },
+    If {
+        /// Line, condition and body of the `if` and `elif` branches.
+        branches: Vec<(usize, Expr, Vec<Node>)>,
+        otherwise: Vec<Node>,
+    },
+    For {
+        line: usize,
+        name: String,
+        list: Expr,
+        body: Vec<Node>,
+    },
+    Include {
+        line: usize,
+        name: String,
+    },
+    Extends {
+        line: usize,
+        name: String,
+    },
+}
+
+/// Template named by an `include` or `extends` directive.
+pub(super) struct Dependency {
+    pub line: usize,
+    pub name: String,
+    pub extends: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum Expr {
+    Literal(Value),
+    Variable(String),
+    Field(Box<Expr>, String),
+    Index(Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    Binary(Box<Expr>, String, Box<Expr>),
+}
+
+/// Error on `line` of the template, pointing at its text.
+pub(super) fn error(lines: &[&str], line: usize, message: impl std::fmt::Display) -> anyhow::Error {
+    anyhow!(
+        "{}\n^ {message} on line {}",
+        lines.get(line).map_or("", |text| text.trim()),
+        line + 1
+    )
+}
+
+/// Expression of a `{% keyword expression %}` line, if `line` is a directive.
+fn directive(line: &str) -> Option<(&str, &str)> {
+    let inner = line.trim().strip_prefix("{%")?.strip_suffix("%}")?.trim();
+    Some(inner.split_once(char::is_whitespace).unwrap_or((inner, "")))
+}
+
+/// `{% keyword expression %}` ending a block.
+type End<'a> = Option<(&'a str, &'a str)>;
+
+/// Parses lines from `*index` up to one of the `ends` directives, which is
+/// returned with its expression.
+fn parse_block<'a>(
+    lines: &[&'a str],
+    index: &mut usize,
+    ends: &[&str],
+) -> Result<(Vec<Node>, End<'a>)> {
+    let mut nodes = vec![];
+
+    while *index < lines.len() {
+        let line = *index;
+        *index += 1;
+
+        let Some((keyword, rest)) = directive(lines[line]) else {
+            nodes.push(Node::Text {
+                line,
+                text: lines[line].to_owned(),
+            });
+            continue;
+        };
+
+        match keyword {
+            "if" => {
+                let mut branches = vec![];
+                let mut otherwise = vec![];
+                let mut at = line;
+                let mut condition = parse(rest).map_err(|err| error(lines, at, err))?;
+
+                loop {
+                    let (body, end) = parse_block(lines, index, &["elif", "else", "endif"])?;
+                    match end {
+                        Some(("elif", rest)) => {
+                            branches.push((at, condition, body));
+                            at = *index - 1;
+                            condition = parse(rest).map_err(|err| error(lines, at, err))?;
+                        }
+                        Some(("else", _)) => {
+                            branches.push((at, condition, body));
+                            let (body, end) = parse_block(lines, index, &["endif"])?;
+                            if end.is_none() {
+                                return Err(error(lines, line, "Unclosed `if`"));
+                            }
+                            otherwise = body;
+                            break;
+                        }
+                        Some(_) => {
+                            branches.push((at, condition, body));
+                            break;
+                        }
+                        None => return Err(error(lines, line, "Unclosed `if`")),
+                    }
+                }
+
+                nodes.push(Node::If {
+                    branches,
+                    otherwise,
+                });
+            }
+
+            "for" => {
+                let (name, list) = rest
+                    .split_once(" in ")
+                    .map(|(name, list)| (name.trim(), list))
+                    .filter(|(name, _)| is_identifier(name))
+                    .ok_or(error(lines, line, "Expected `for <name> in <list>`"))?;
+                let list = parse(list).map_err(|err| error(lines, line, err))?;
+
+                let (body, end) = parse_block(lines, index, &["endfor"])?;
+                if end.is_none() {
+                    return Err(error(lines, line, "Unclosed `for`"));
+                }
+
+                nodes.push(Node::For {
+                    line,
+                    name: name.to_owned(),
+                    list,
+                    body,
+                });
+            }
+
+            "include" | "extends" => {
+                let name = match parse(rest) {
+                    Ok(Expr::Literal(Value::String(name))) => name,
+                    _ => return Err(error(lines, line, "Expected a quoted template name")),
+                };
+
+                nodes.push(if keyword == "include" {
+                    Node::Include { line, name }
+                } else if ends.is_empty() {
+                    Node::Extends { line, name }
+                } else {
+                    return Err(error(lines, line, "`extends` must be outside of blocks"));
+                });
+            }
+
+            keyword if ends.contains(&keyword) => return Ok((nodes, Some((keyword, rest)))),
+
+            keyword => {
+                return Err(error(
+                    lines,
+                    line,
+                    format!("Unexpected `{keyword}` directive"),
+                ))
+            }
+        }
+    }
+
+    Ok((nodes, None))
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    String(String),
+    Identifier(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 17] = [
+    "==", "!=", "<=", ">=", "<", ">", "+", "-", "*", "/", "%", "(", ")", "[", "]", ".", ",",
+];
+
+fn tokenize(expression: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut rest = expression.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Number(
+                rest[..end]
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid number `{}`", &rest[..end]))?,
+            ));
+            rest = &rest[end..];
+        } else if c == '"' || c == '\'' {
+            let end = rest[1..].find(c).ok_or(anyhow!("Unterminated string"))?;
+            tokens.push(Token::String(rest[1..end + 1].to_owned()));
+            rest = &rest[end + 2..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Identifier(rest[..end].to_owned()));
+            rest = &rest[end..];
+        } else {
+            let symbol = SYMBOLS
+                .into_iter()
+                .find(|symbol| rest.starts_with(symbol))
+                .ok_or(anyhow!("Unexpected `{c}`"))?;
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        }
+
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+/// Precedence climbing parser over the tokens of an expression.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get_mut(self.position)?;
+        self.position += 1;
+        Some(std::mem::replace(token, Token::Symbol("")))
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol)
+            || matches!(self.peek(), Some(Token::Identifier(word)) if word == symbol);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<()> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(anyhow!("Expected `{symbol}`"))
+        }
+    }
+
+    fn binary(
+        &mut self,
+        operators: &[&str],
+        operand: fn(&mut Self) -> Result<Expr>,
+    ) -> Result<Expr> {
+        let mut left = operand(self)?;
+
+        'outer: loop {
+            for operator in operators {
+                if self.eat(operator) {
+                    left = Expr::Binary(
+                        Box::new(left),
+                        operator.to_string(),
+                        Box::new(operand(self)?),
+                    );
+                    continue 'outer;
+                }
+            }
+
+            return Ok(left);
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        self.binary(&["or"], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        self.binary(&["and"], Self::not)
+    }
+
+    fn not(&mut self) -> Result<Expr> {
+        if self.eat("not") {
+            Ok(Expr::Not(Box::new(self.not()?)))
+        } else {
+            self.comparison()
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        self.binary(&["==", "!=", "<=", ">=", "<", ">"], Self::sum)
+    }
+
+    fn sum(&mut self) -> Result<Expr> {
+        self.binary(&["+", "-"], Self::product)
+    }
+
+    fn product(&mut self) -> Result<Expr> {
+        self.binary(&["*", "/", "%"], Self::unary)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat("-") {
+            Ok(Expr::Negate(Box::new(self.unary()?)))
+        } else {
+            self.postfix()
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Expr> {
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.eat(".") {
+                match self.next() {
+                    Some(Token::Identifier(field)) => expr = Expr::Field(Box::new(expr), field),
+                    Some(Token::Number(index)) if index.fract() == 0.0 => {
+                        expr = Expr::Index(Box::new(expr), Box::new(Expr::Literal(index.into())))
+                    }
+                    _ => return Err(anyhow!("Expected a field name after `.`")),
+                }
+            } else if self.eat("[") {
+                let index = self.or()?;
+                self.expect("]")?;
+                expr = Expr::Index(Box::new(expr), Box::new(index));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok(Expr::Literal(number.into())),
+            Some(Token::String(string)) => Ok(Expr::Literal(string.into())),
+            Some(Token::Identifier(name)) => match name.as_str() {
+                "true" => Ok(Expr::Literal(true.into())),
+                "false" => Ok(Expr::Literal(false.into())),
+                "null" => Ok(Expr::Literal(Value::Null)),
+                _ if self.eat("(") => {
+                    let mut arguments = vec![];
+                    if !self.eat(")") {
+                        loop {
+                            arguments.push(self.or()?);
+                            if self.eat(")") {
+                                break;
+                            }
+                            self.expect(",")?;
+                        }
+                    }
+                    Ok(Expr::Call(name, arguments))
+                }
+                _ => Ok(Expr::Variable(name)),
+            },
+            Some(Token::Symbol("(")) => {
+                let expr = self.or()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Symbol(symbol)) => Err(anyhow!("Unexpected `{symbol}`")),
+            None => Err(anyhow!("Unexpected end of expression")),
+        }
+    }
+}
+
+fn parse(expression: &str) -> Result<Expr> {
+    let mut parser = Parser {
+        tokens: tokenize(expression)?,
+        position: 0,
+    };
+
+    let expr = parser.or()?;
+    if parser.peek().is_some() {
+        return Err(anyhow!(
+            "Unexpected tokens after expression `{}`",
+            expression.trim()
+        ));
+    }
+
+    Ok(expr)
+}
+
+/// Variables of the enclosing loops, over the root context.
+struct Scope<'a> {
+    context: &'a Value,
+    variables: Vec<(String, Value)>,
+}
+
+impl Scope<'_> {
+    fn get(&self, name: &str) -> Result<Value> {
+        self.variables
+            .iter()
+            .rev()
+            .find(|(variable, _)| variable == name)
+            .map(|(_, value)| value)
+            .or(self.context.get(name))
+            .cloned()
+            .ok_or(anyhow!("Unknown variable `{name}`"))
+    }
+}
+
+fn number(value: &Value) -> Result<f64> {
+    value
+        .as_f64()
+        .ok_or(anyhow!("Expected a number, found `{value}`"))
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(bool) => *bool,
+        Value::Number(number) => number.as_f64() != Some(0.0),
+        Value::String(string) => !string.is_empty(),
+        Value::Array(array) => !array.is_empty(),
+        Value::Object(object) => !object.is_empty(),
+    }
+}
+
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn evaluate(expr: &Expr, scope: &Scope) -> Result<Value> {
+    Ok(match expr {
+        Expr::Literal(value) => value.clone(),
+        Expr::Variable(name) => scope.get(name)?,
+
+        Expr::Field(expr, field) => match evaluate(expr, scope)? {
+            Value::Object(object) => object
+                .get(field)
+                .cloned()
+                .ok_or(anyhow!("Unknown field `{field}`"))?,
+            value => return Err(anyhow!("`{value}` has no field `{field}`")),
+        },
+
+        Expr::Index(expr, index) => {
+            let value = evaluate(expr, scope)?;
+            match (&value, evaluate(index, scope)?) {
+                (Value::Array(array), Value::Number(index)) => index
+                    .as_f64()
+                    .filter(|index| *index >= 0.0)
+                    .and_then(|index| array.get(index as usize))
+                    .cloned()
+                    .unwrap_or(Value::Null),
+                (Value::Object(object), Value::String(key)) => {
+                    object.get(&key).cloned().unwrap_or(Value::Null)
+                }
+                (_, index) => return Err(anyhow!("Unable to index `{value}` with `{index}`")),
+            }
+        }
+
+        Expr::Call(name, arguments) => {
+            let arguments = arguments
+                .iter()
+                .map(|argument| evaluate(argument, scope))
+                .collect::<Result<Vec<_>>>()?;
+
+            match (name.as_str(), &arguments[..]) {
+                ("len", [Value::Array(array)]) => array.len().into(),
+                ("len", [Value::String(string)]) => string.chars().count().into(),
+                ("abs", [value]) => number(value)?.abs().into(),
+                ("round", [value]) => number(value)?.round().into(),
+                ("round", [value, decimals]) => {
+                    let scale = 10f64.powi(number(decimals)? as i32);
+                    ((number(value)? * scale).round() / scale).into()
+                }
+                ("min", [a, b]) => number(a)?.min(number(b)?).into(),
+                ("max", [a, b]) => number(a)?.max(number(b)?).into(),
+                _ => {
+                    return Err(anyhow!(
+                        "Unknown function `{name}` for {} arguments",
+                        arguments.len()
+                    ))
+                }
+            }
+        }
+
+        Expr::Not(expr) => (!truthy(&evaluate(expr, scope)?)).into(),
+        Expr::Negate(expr) => (-number(&evaluate(expr, scope)?)?).into(),
+
+        Expr::Binary(left, operator, right) => {
+            let left = evaluate(left, scope)?;
+
+            // Short-circuit logic returns the deciding operand
+            match operator.as_str() {
+                "and" if !truthy(&left) => return Ok(left),
+                "or" if truthy(&left) => return Ok(left),
+                "and" | "or" => return evaluate(right, scope),
+                _ => {}
+            }
+
+            let right = evaluate(right, scope)?;
+            match operator.as_str() {
+                "==" => equal(&left, &right).into(),
+                "!=" => (!equal(&left, &right)).into(),
+                "+" if left.is_string() || right.is_string() => {
+                    format!("{}{}", display(&left)?, display(&right)?).into()
+                }
+                "<" | "<=" | ">" | ">=" => {
+                    let ordering = match (&left, &right) {
+                        (Value::String(a), Value::String(b)) => a.cmp(b),
+                        _ => number(&left)?
+                            .partial_cmp(&number(&right)?)
+                            .ok_or(anyhow!("Unable to compare `{left}` and `{right}`"))?,
+                    };
+                    match operator.as_str() {
+                        "<" => ordering.is_lt(),
+                        "<=" => ordering.is_le(),
+                        ">" => ordering.is_gt(),
+                        _ => ordering.is_ge(),
+                    }
+                    .into()
+                }
+                _ => {
+                    let (a, b) = (number(&left)?, number(&right)?);
+                    match operator.as_str() {
+                        "+" => a + b,
+                        "-" => a - b,
+                        "*" => a * b,
+                        "/" => a / b,
+                        _ => a % b,
+                    }
+                    .into()
+                }
+            }
+        }
+    })
+}
+
+/// Text of a value in the output. Numbers are written with the precision of
+/// the Elumatec format, booleans as `1` and `0`.
+fn display(value: &Value) -> Result<String> {
+    Ok(match value {
+        Value::Null => String::new(),
+        Value::Bool(bool) => (*bool as i32).to_string(),
+        Value::Number(number) => match number.as_f64() {
+            Some(float) if float.fract() == 0.0 && float.abs() < 1e15 => (float as i64).to_string(),
+            Some(float) => (float as f32).to_string(),
+            None => number.to_string(),
+        },
+        Value::String(string) => string.clone(),
+        Value::Array(_) | Value::Object(_) => {
+            return Err(anyhow!("Unable to write `{value}` as text"))
+        }
+    })
+}
+
+/// Replaces the `{{ expression }}` placeholders of `text`.
+fn substitute(text: &str, scope: &Scope) -> Result<String> {
+    let mut output = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..].find("}}").ok_or(anyhow!("Unclosed `{{{{`"))?;
+
+        output.push_str(&rest[..start]);
+        output.push_str(&display(&evaluate(
+            &parse(&rest[start + 2..start + end])?,
+            scope,
+        )?)?);
+        rest = &rest[start + end + 2..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn render_nodes(
+    nodes: &[Node],
+    lines: &[&str],
+    template: &Template,
+    scope: &mut Scope,
+    output: &mut Vec<String>,
+) -> Result<()> {
+    for node in nodes {
+        match node {
+            Node::Text { line, text } => {
+                output.push(substitute(text, scope).map_err(|err| error(lines, *line, err))?)
+            }
+
+            Node::If {
+                branches,
+                otherwise,
+            } => {
+                let mut body = otherwise;
+                for (line, condition, branch) in branches {
+                    let value =
+                        evaluate(condition, scope).map_err(|err| error(lines, *line, err))?;
+                    if truthy(&value) {
+                        body = branch;
+                        break;
+                    }
+                }
+
+                render_nodes(body, lines, template, scope, output)?;
+            }
+
+            Node::Include { line, name } => {
+                let included = template
+                    .includes
+                    .iter()
+                    .find(|included| included.name == *name)
+                    .ok_or(error(
+                        lines,
+                        *line,
+                        format!("Template `{name}` is not loaded"),
+                    ))?;
+
+                render_template(included, scope, output).map_err(|err| {
+                    err.context(error(
+                        lines,
+                        *line,
+                        format!("Unable to render included template `{name}`"),
+                    ))
+                })?;
+            }
+
+            // Tags of the base template are merged once rendered
+            Node::Extends { .. } => {}
+
+            Node::For {
+                line,
+                name,
+                list,
+                body,
+            } => {
+                let items = match evaluate(list, scope).map_err(|err| error(lines, *line, err))? {
+                    Value::Array(items) => items,
+                    Value::Null => vec![],
+                    value => return Err(error(lines, *line, format!("`{value}` is not a list"))),
+                };
+
+                let count = items.len();
+                for (index, item) in items.into_iter().enumerate() {
+                    let mut info = Map::new();
+                    info.insert("index".to_owned(), (index + 1).into());
+                    info.insert("count".to_owned(), count.into());
+                    info.insert("first".to_owned(), (index == 0).into());
+                    info.insert("last".to_owned(), (index + 1 == count).into());
+
+                    scope
+                        .variables
+                        .push(("loop".to_owned(), Value::Object(info)));
+                    scope.variables.push((name.clone(), item));
+                    let rendered = render_nodes(body, lines, template, scope, output);
+                    scope.variables.truncate(scope.variables.len() - 2);
+                    rendered?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Templates included or extended by `template`, in order.
+pub(super) fn dependencies(template: &str) -> Result<Vec<Dependency>> {
+    fn walk(nodes: &[Node], dependencies: &mut Vec<Dependency>) {
+        for node in nodes {
+            match node {
+                Node::Include { line, name } | Node::Extends { line, name } => {
+                    dependencies.push(Dependency {
+                        line: *line,
+                        name: name.clone(),
+                        extends: matches!(node, Node::Extends { .. }),
+                    })
+                }
+                Node::If {
+                    branches,
+                    otherwise,
+                } => {
+                    for (_, _, body) in branches {
+                        walk(body, dependencies);
+                    }
+                    walk(otherwise, dependencies);
+                }
+                Node::For { body, .. } => walk(body, dependencies),
+                Node::Text { .. } => {}
+            }
+        }
+    }
+
+    let lines = template.split('\n').collect::<Vec<_>>();
+    let (nodes, _) = parse_block(&lines, &mut 0, &[])?;
+
+    let mut dependencies = vec![];
+    walk(&nodes, &mut dependencies);
+
+    if let Some(second) = dependencies
+        .iter()
+        .filter(|dependency| dependency.extends)
+        .nth(1)
+    {
+        return Err(error(
+            &lines,
+            second.line,
+            "A template can only extend one template",
+        ));
+    }
+
+    Ok(dependencies)
+}
+
+fn render_template(template: &Template, scope: &mut Scope, output: &mut Vec<String>) -> Result<()> {
+    let lines = template.contents.split('\n').collect::<Vec<_>>();
+    let (nodes, _) = parse_block(&lines, &mut 0, &[])?;
+
+    render_nodes(&nodes, &lines, template, scope, output)
+}
+
+/// Renders `template` with the values of `context`.
+pub(super) fn render(template: &Template, context: &Value) -> Result<String> {
+    let mut output = vec![];
+    let mut scope = Scope {
+        context,
+        variables: vec![],
+    };
+    render_template(template, &mut scope, &mut output)?;
+
+    Ok(output.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::Origin;
+    use serde_json::json;
+
+    fn template(contents: &str) -> Template {
+        Template {
+            name: "test".to_owned(),
+            origin: Origin::Builtin,
+            contents: contents.to_owned(),
+            includes: vec![],
+            base: None,
+        }
+    }
+
+    #[test]
+    fn evaluate_expressions() {
+        let context = json!({
+            "part": { "length": 1250.5, "left": { "h": 45.0 } },
+            "parts": [1, 2, 3],
+            "name": "L100",
+        });
+        let scope = Scope {
+            context: &context,
+            variables: vec![],
+        };
+        let eval = |expression: &str| {
+            display(&evaluate(&parse(expression).unwrap(), &scope).unwrap()).unwrap()
+        };
+
+        assert_eq!(eval("part.length * 2 - 1"), "2500");
+        assert_eq!(eval("(1 + 2) * 3 % 4"), "1");
+        assert_eq!(eval("round(part.length / 3, 2)"), "416.83");
+        assert_eq!(eval("part.left.h < 90 and len(parts) == 3"), "1");
+        assert_eq!(eval("not parts or 'none'"), "none");
+        assert_eq!(eval("name + '_' + parts[1]"), "L100_2");
+        assert_eq!(eval("-abs(-2.5)"), "-2.5");
+        assert_eq!(eval("78.47212"), "78.47212");
+
+        assert!(parse("part.").is_err());
+        assert!(parse("1 +").is_err());
+        assert!(parse("(1").is_err());
+        assert!(evaluate(&parse("missing").unwrap(), &scope).is_err());
+    }
+
+    #[test]
+    fn render_loops_and_conditionals() {
+        let template = "\
+:OPTIONS
+OCreator = \"{{ creator }}\"
+{% for part in parts %}
+:CUT
+CNo = {{ loop.index }}
+CCount = {{ loop.count }}
+{% if part.angle == 90 %}
+CAngleLH = 90
+{% elif part.angle > 90 %}
+CAngleLH = {{ 180 - part.angle }}
+{% else %}
+CAngleLH = {{ part.angle }}
+{% endif %}
+{% endfor %}";
+
+        let context = json!({
+            "creator": "Elucad",
+            "parts": [{ "angle": 90 }, { "angle": 135 }, { "angle": 22.5 }],
+        });
+
+        assert_eq!(
+            render(&self::template(template), &context).unwrap(),
+            "\
+:OPTIONS
+OCreator = \"Elucad\"
+:CUT
+CNo = 1
+CCount = 3
+CAngleLH = 90
+:CUT
+CNo = 2
+CCount = 3
+CAngleLH = 45
+:CUT
+CNo = 3
+CCount = 3
+CAngleLH = 22.5"
+        );
+
+        // Static templates are left as they are
+        let template = ":JOB\nVar0 = 0.000000\n";
+        assert_eq!(
+            render(&self::template(template), &json!({})).unwrap(),
+            template
+        );
+    }
+
+    #[test]
+    fn report_line_of_errors() {
+        let error = |template: &str| {
+            render(&self::template(template), &json!({ "parts": [] }))
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            error(":JOB\n{% for part in parts %}\nCNo = 1\n"),
+            "{% for part in parts %}\n^ Unclosed `for` on line 2"
+        );
+        assert_eq!(
+            error(":JOB\n{% endif %}"),
+            "{% endif %}\n^ Unexpected `endif` directive on line 2"
+        );
+        assert_eq!(
+            error(":JOB\ninfo = \"{{ project }}\""),
+            "info = \"{{ project }}\"\n^ Unknown variable `project` on line 2"
+        );
+        assert_eq!(
+            error("{% if 1 + %}\n{% endif %}"),
+            "{% if 1 + %}\n^ Unexpected end of expression on line 1"
+        );
+    }
+}
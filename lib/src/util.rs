@@ -1,8 +1,11 @@
-use std::{fs, path::Path};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
-use anyhow::Result;
-use reqwest::{blocking::Client, Url};
-use serde_json::Value;
+use anyhow::{anyhow, Result};
+
+use crate::{cover::Project, CoverClient};
 
 pub enum ProjectInfo {
     Uuid(String),
@@ -10,51 +13,102 @@ pub enum ProjectInfo {
     Name(String),
 }
 
-pub fn get_project_uuid(project_info: ProjectInfo) -> Option<String> {
-    let base_url = Url::parse(&std::env::var("BASE_URL").expect("BASE_URL is not set"))
-        .expect("Invalid URL provided as BASE_URL");
-    println!("Cover API : {}", base_url.to_string());
-    let client = Client::new();
+/// Projects matching `project_info`. Names only select several projects,
+/// those named exactly as asked being kept when there are some.
+pub fn find_projects(client: &CoverClient, project_info: ProjectInfo) -> Result<Vec<Project>> {
+    println!("Cover API : {}", client.url());
 
-    match project_info {
-        ProjectInfo::Id(id) => {
-            let url = base_url.join("/project/search/findById").unwrap();
-            let res = client.get(url).query(&[("id", id)]).send().unwrap();
+    Ok(match project_info {
+        ProjectInfo::Id(id) => client.find_by_id(id.into())?.into_iter().collect(),
+        ProjectInfo::Name(name) => prefer_exact(&name, client.find_by_name(&name)?),
+        ProjectInfo::Uuid(uuid) => client.find_by_uuid(&uuid)?.into_iter().collect(),
+    })
+}
 
-            if let Ok(json) = res.json::<Value>() {
-                Some(json["uuid"].as_str().unwrap().to_owned())
-            } else {
-                None
-            }
-        }
+/// UUID of the single project matching `project_info`. Fails when several
+/// projects match, `find_projects` listing them.
+pub fn get_project_uuid(client: &CoverClient, project_info: ProjectInfo) -> Result<Option<String>> {
+    let mut projects = find_projects(client, project_info)?;
+    if projects.len() > 1 {
+        return Err(anyhow!(
+            "Several projects match : {}",
+            projects
+                .iter()
+                .map(|project| format!("`{}` ({})", project.name, project.uuid))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
 
-        ProjectInfo::Name(name) => {
-            let url = base_url.join("/project/search/findByName").unwrap();
-            let res = client.get(url).query(&[("name", &name)]).send().unwrap();
+    Ok(projects.pop().map(|project| project.uuid))
+}
 
-            if let Ok(json) = res.json::<Value>() {
-                let projects = json.as_array().unwrap();
-                if projects.is_empty() {
-                    None
-                } else {
-                    let project = &projects[0];
-                    Some(project["uuid"].as_str().unwrap().to_owned())
-                }
-            } else {
-                None
-            }
-        }
+/// Criteria of listed projects, all of those set being met.
+#[derive(Clone, Debug, Default)]
+pub struct ProjectFilter {
+    /// Text the name contains, case aside.
+    pub name: Option<String>,
 
-        ProjectInfo::Uuid(uuid) => {
-            let url = base_url.join("/project/search/findByUuid").unwrap();
-            let res = client.get(url).query(&[("uuid", &uuid)]).send().unwrap();
+    /// First and last dates, as `YYYY-MM-DD` or any prefix of the ISO 8601
+    /// dates of the API.
+    pub since: Option<String>,
+    pub until: Option<String>,
 
-            if res.status().is_success() {
-                Some(uuid)
-            } else {
-                None
-            }
-        }
+    /// Status, case aside.
+    pub status: Option<String>,
+}
+
+impl ProjectFilter {
+    /// Whether every project matches.
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.since.is_none() && self.until.is_none() && self.status.is_none()
+    }
+
+    pub fn matches(&self, project: &Project) -> bool {
+        let contains = |text: &str, part: &str| text.to_lowercase().contains(&part.to_lowercase());
+
+        let date = project.date();
+        let within = |bound: &Option<String>, inside: fn(&str, &str) -> bool| match (bound, &date) {
+            (None, _) => true,
+            (Some(bound), Some(date)) => inside(date.get(..bound.len()).unwrap_or(date), bound),
+            (Some(_), None) => false,
+        };
+
+        self.name
+            .as_ref()
+            .is_none_or(|name| contains(&project.name, name))
+            && within(&self.since, |date, since| date >= since)
+            && within(&self.until, |date, until| date <= until)
+            && self.status.as_ref().is_none_or(|status| {
+                project
+                    .status()
+                    .is_some_and(|other| other.eq_ignore_ascii_case(status))
+            })
+    }
+}
+
+/// Projects of the API meeting `filter`, in the order of the API.
+pub fn list_projects(client: &CoverClient, filter: &ProjectFilter) -> Result<Vec<Project>> {
+    Ok(client
+        .projects()?
+        .into_iter()
+        .filter(|project| filter.matches(project))
+        .collect())
+}
+
+/// Projects named `name`, case aside, or all of `projects` if none is.
+pub(crate) fn prefer_exact(name: &str, projects: Vec<Project>) -> Vec<Project> {
+    let name = name.trim();
+    let exact = projects
+        .iter()
+        .filter(|project| project.name.trim().eq_ignore_ascii_case(name))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    if exact.is_empty() {
+        projects
+    } else {
+        exact
     }
 }
 
@@ -111,31 +165,184 @@ pub fn move_files_with_extensions(folder: &str, dest: &str, extension: &str) ->
     Ok(())
 }
 
-// #[cfg(test)]
-#[allow(dead_code)]
-#[allow(unused_imports)]
-mod tests {
+/// Replaces `{key}` placeholders of `pattern`, keeping the result filename-safe.
+pub fn render_file_name(pattern: &str, fields: &[(&str, &str)]) -> String {
+    let mut file_name = pattern.to_owned();
+    for (key, value) in fields {
+        let value = value
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+
+        file_name = file_name.replace(&format!("{{{key}}}"), &value);
+    }
+
+    file_name
+}
+
+/// Whether `output` names a directory : an existing one, or any path ending
+/// with a separator.
+pub fn is_directory(output: &str) -> bool {
+    output.ends_with(['/', std::path::MAIN_SEPARATOR]) || Path::new(output).is_dir()
+}
+
+/// Path of the job `name` : inside `output` if it is a directory,
+/// next to it otherwise, keeping its extension.
+pub fn job_path(output: &str, name: &str, default_extension: &str) -> PathBuf {
+    if is_directory(output) {
+        return Path::new(output).join(format!("{name}.{default_extension}"));
+    }
+
+    let output = Path::new(output);
+
+    match output.extension() {
+        Some(extension) => output.with_file_name(format!("{name}.{}", extension.to_string_lossy())),
+        None => output.with_file_name(name),
+    }
+}
+
+/// Path of the bill of materials of `project`, next to the machine file `output`.
+pub fn bom_path(output: &str, project: &str, extension: &str) -> PathBuf {
+    let path = Path::new(output);
+    if is_directory(output) {
+        path.join(format!("{project}_bom.{extension}"))
+    } else {
+        path.with_file_name(format!("{}_bom.{extension}", file_stem(output)))
+    }
+}
+
+/// Whether `path` is a bill of materials written by `bom_path`.
+pub fn is_bom(path: &str) -> bool {
+    file_stem(path).ends_with("_bom")
+}
+
+/// Lowercase hexadecimal of `bytes`.
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub fn file_stem(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
     // UUID 0488bf92-813f-4bbd-8e5f-16885d5b75df
     // Name import
     // ID 8
 
     use super::*;
+    use crate::config::{Api, FixtureMode, Fixtures};
 
-    fn set_base_url() {
-        std::env::set_var("BASE_URL", "http://localhost:5000");
+    /// Client answered by the hand-written responses of `tests/fixtures/cover`.
+    /// They follow the payloads the client expects, not ones recorded from
+    /// Cover, so the tests using it do not check the client against the API.
+    pub(crate) fn client() -> CoverClient {
+        CoverClient::new(&Api {
+            url: Some("http://localhost:5000".to_owned()),
+            fixtures: Some(Fixtures {
+                directory: Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/cover"),
+                mode: FixtureMode::Replay,
+            }),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn render_pattern() {
+        let name = render_file_name(
+            "{project}_{profile}_{bar}",
+            &[("project", "job"), ("profile", "L 100/2"), ("bar", "3")],
+        );
+        assert_eq!(name, "job_L_100_2_3");
+    }
+
+    #[test]
+    fn job_path_next_to_output_file() {
+        assert_eq!(
+            job_path("out/job.ncw", "job_F1", "ncw"),
+            PathBuf::from("out/job_F1.ncw")
+        );
+        assert_eq!(job_path("job", "job", "ncw"), PathBuf::from("job"));
+        assert_eq!(
+            job_path("missing/", "job", "ncw"),
+            PathBuf::from("missing/job.ncw")
+        );
+    }
+
+    #[test]
+    fn prefer_exact_names() {
+        let project = |uuid: &str, name: &str| Project {
+            uuid: uuid.to_owned(),
+            name: name.to_owned(),
+            ..Default::default()
+        };
+        let projects = vec![
+            project("1", "import"),
+            project("2", "Import 2"),
+            project("3", "IMPORT "),
+        ];
+
+        let uuids = |projects: Vec<Project>| {
+            projects
+                .into_iter()
+                .map(|project| project.uuid)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(uuids(prefer_exact("Import", projects.clone())), ["1", "3"]);
+        assert_eq!(uuids(prefer_exact("Import 2", projects.clone())), ["2"]);
+        assert_eq!(uuids(prefer_exact("port", projects)), ["1", "2", "3"]);
+    }
+
+    #[test]
+    fn filter_projects() {
+        let project: Project = serde_json::from_value(serde_json::json!({
+            "uuid": "1",
+            "name": "Import Dupont",
+            "updatedAt": "2026-03-15T10:00:00Z",
+            "status": "READY",
+        }))
+        .unwrap();
+
+        let filter = |name: &str, since: &str, until: &str, status: &str| {
+            let set = |value: &str| Some(value.to_owned()).filter(|value| !value.is_empty());
+            ProjectFilter {
+                name: set(name),
+                since: set(since),
+                until: set(until),
+                status: set(status),
+            }
+            .matches(&project)
+        };
+
+        assert!(filter("", "", "", ""));
+        assert!(filter("dupont", "2026-03-15", "2026-03-15", "ready"));
+        assert!(filter("", "2026-03", "2026", ""));
+        assert!(!filter("durand", "", "", ""));
+        assert!(!filter("", "2026-03-16", "", ""));
+        assert!(!filter("", "", "2026-03-14", ""));
+        assert!(!filter("", "", "", "draft"));
     }
 
     #[test]
     fn by_id_should_return_none() {
-        set_base_url();
-        let res = get_project_uuid(ProjectInfo::Id(1000));
+        let res = get_project_uuid(&client(), ProjectInfo::Id(1000)).unwrap();
         assert!(res.is_none());
     }
 
     #[test]
     fn by_id() {
-        set_base_url();
-        let uuid = get_project_uuid(ProjectInfo::Id(8));
+        let uuid = get_project_uuid(&client(), ProjectInfo::Id(8)).unwrap();
         assert!(uuid.is_some());
 
         let uuid = uuid.unwrap();
@@ -144,15 +351,17 @@ mod tests {
 
     #[test]
     fn by_name_should_return_none() {
-        set_base_url();
-        let res = get_project_uuid(ProjectInfo::Name("inexistent-project".to_owned()));
+        let res = get_project_uuid(
+            &client(),
+            ProjectInfo::Name("inexistent-project".to_owned()),
+        )
+        .unwrap();
         assert!(res.is_none());
     }
 
     #[test]
     fn by_name() {
-        set_base_url();
-        let uuid = get_project_uuid(ProjectInfo::Name("import".to_owned()));
+        let uuid = get_project_uuid(&client(), ProjectInfo::Name("import".to_owned())).unwrap();
         assert!(uuid.is_some());
 
         let uuid = uuid.unwrap();
@@ -161,17 +370,18 @@ mod tests {
 
     #[test]
     fn by_uuid_should_return_none() {
-        set_base_url();
-        let res = get_project_uuid(ProjectInfo::Uuid("non-existent-uuid".to_owned()));
+        let res =
+            get_project_uuid(&client(), ProjectInfo::Uuid("non-existent-uuid".to_owned())).unwrap();
         assert!(res.is_none());
     }
 
     #[test]
     fn by_uuid() {
-        set_base_url();
-        let uuid = get_project_uuid(ProjectInfo::Uuid(
-            "0488bf92-813f-4bbd-8e5f-16885d5b75df".to_owned(),
-        ));
+        let uuid = get_project_uuid(
+            &client(),
+            ProjectInfo::Uuid("0488bf92-813f-4bbd-8e5f-16885d5b75df".to_owned()),
+        )
+        .unwrap();
         assert!(uuid.is_some());
 
         let uuid = uuid.unwrap();
@@ -0,0 +1,418 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    cache::{now, ProjectCache},
+    config::Watch,
+    cover::{Project, ProjectData},
+    util::{hex, list_projects, ProjectFilter},
+    CoverClient, ExportOptions, Exporter, Source,
+};
+
+/// Status of the projects exported when `watch.status` is not set.
+const DEFAULT_STATUS: &str = "READY_FOR_PRODUCTION";
+
+/// Seconds between two polls when `watch.interval` is not set.
+const DEFAULT_INTERVAL: u64 = 60;
+
+/// Folder of the spool exports are written to before being moved.
+const PARTIAL: &str = ".partial";
+
+/// Exports the projects reaching a status into a spool folder, once per
+/// version of their data.
+pub struct Watcher<'a> {
+    client: &'a CoverClient,
+    exporter: &'a Exporter,
+    cache: &'a ProjectCache,
+    options: ExportOptions,
+
+    vendor: String,
+    status: String,
+    interval: Duration,
+    spool: PathBuf,
+    state: PathBuf,
+}
+
+/// Projects already exported, by uuid, saved between polls.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct WatchState {
+    pub projects: BTreeMap<String, Exported>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Exported {
+    pub name: String,
+
+    /// Update date of the project when it was last checked.
+    pub date: Option<String>,
+
+    /// SHA-256 of the project data exported.
+    pub hash: String,
+
+    /// When the project was exported, in seconds since the Unix epoch.
+    pub exported: u64,
+    pub files: Vec<PathBuf>,
+}
+
+impl<'a> Watcher<'a> {
+    pub fn new(
+        client: &'a CoverClient,
+        exporter: &'a Exporter,
+        cache: &'a ProjectCache,
+        watch: &Watch,
+        options: ExportOptions,
+    ) -> Result<Self> {
+        let vendor = watch.vendor.clone().ok_or(anyhow!(
+            "No provider to export projects for : use `watch.vendor` or `--vendor`"
+        ))?;
+        let spool = watch.spool.clone().ok_or(anyhow!(
+            "No spool folder to export projects to : use `watch.spool` or `--spool`"
+        ))?;
+
+        // Projects share the spool : their files must not overwrite each other
+        if let Some(pattern) = options
+            .name_pattern
+            .as_ref()
+            .filter(|pattern| !pattern.contains("{project}"))
+        {
+            return Err(anyhow!(
+                "Name pattern `{pattern}` lacks `{{project}}` : projects would overwrite each other in the spool"
+            ));
+        }
+
+        Ok(Self {
+            client,
+            exporter,
+            cache,
+            options,
+            vendor,
+            status: watch.status.clone().unwrap_or(DEFAULT_STATUS.to_owned()),
+            interval: Duration::from_secs(watch.interval.unwrap_or(DEFAULT_INTERVAL)),
+            state: watch
+                .state
+                .clone()
+                .unwrap_or(spool.join(".watch-state.json")),
+            spool,
+        })
+    }
+
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn spool(&self) -> &Path {
+        &self.spool
+    }
+
+    /// Exports the projects of the status that are new, or whose data changed
+    /// since they were exported, and returns them. Projects failing to export
+    /// are reported on stderr, and tried again at the next poll. Projects no
+    /// longer of the status are forgotten.
+    pub fn poll(&self) -> Result<Vec<Project>> {
+        let projects = list_projects(
+            self.client,
+            &ProjectFilter {
+                status: Some(self.status.clone()),
+                ..Default::default()
+            },
+        )?;
+
+        let mut state = WatchState::read(&self.state)?;
+        state
+            .projects
+            .retain(|uuid, _| projects.iter().any(|project| &project.uuid == uuid));
+        let mut exported = vec![];
+
+        for project in projects {
+            let seen = state.projects.get(&project.uuid);
+
+            // Unchanged update dates spare the download of the data, creation
+            // dates staying the same when it changes
+            if seen
+                .is_some_and(|seen| project.updated().is_some() && seen.date == project.updated())
+            {
+                continue;
+            }
+
+            let entry = match self.export(&project, seen) {
+                Ok(Some(entry)) => {
+                    println!(
+                        "Exported project {} ({}) : {}",
+                        project.uuid,
+                        project.name,
+                        entry
+                            .files
+                            .iter()
+                            .map(|file| file.display().to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                    exported.push(project.clone());
+                    entry
+                }
+
+                // Same data under a new date
+                Ok(None) => Exported {
+                    date: project.updated(),
+                    ..seen.unwrap().clone()
+                },
+
+                Err(err) => {
+                    eprintln!(
+                        "Unable to export project {} ({}) : {err:#}",
+                        project.uuid, project.name
+                    );
+                    continue;
+                }
+            };
+
+            state.projects.insert(project.uuid.clone(), entry);
+        }
+
+        state.write(&self.state)?;
+        Ok(exported)
+    }
+
+    /// Exports `project` to the spool, unless its data is the one of `seen`.
+    fn export(&self, project: &Project, seen: Option<&Exported>) -> Result<Option<Exported>> {
+        let data = self.cache.fetch(self.client, project)?;
+        let hash = hash(&data)?;
+        if seen.is_some_and(|seen| seen.hash == hash) {
+            return Ok(None);
+        }
+
+        // Files only reach the spool once complete
+        let partial = self.spool.join(PARTIAL);
+        if partial.is_dir() {
+            fs::remove_dir_all(&partial)?;
+        }
+        fs::create_dir_all(&partial)
+            .with_context(|| format!("Unable to create spool folder {}", partial.display()))?;
+
+        self.exporter.export(
+            Source::Api {
+                project_uuid: project.uuid.clone(),
+                data,
+            },
+            &self.vendor,
+            Some(partial.to_string_lossy().into_owned()),
+            &self.options,
+        )?;
+
+        let mut files = vec![];
+        for file in fs::read_dir(&partial)? {
+            let file = file?.path();
+            let destination = self.spool.join(file.file_name().unwrap());
+            fs::rename(&file, &destination)
+                .with_context(|| format!("Unable to move {} to the spool", file.display()))?;
+            files.push(destination);
+        }
+        files.sort();
+
+        Ok(Some(Exported {
+            name: project.name.clone(),
+            date: project.updated(),
+            hash,
+            exported: now(),
+            files,
+        }))
+    }
+}
+
+impl WatchState {
+    /// State saved in `path`, empty if there is none.
+    pub fn read(path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read watch state {}", path.display()))?;
+
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid watch state {}", path.display()))
+    }
+
+    /// Saves the state to `path`, replacing the previous one at once.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, serde_json::to_string_pretty(self)? + "\n")
+            .and_then(|_| fs::rename(&temporary, path))
+            .with_context(|| format!("Unable to write watch state {}", path.display()))
+    }
+}
+
+/// SHA-256 of project data, telling versions apart when dates do not.
+fn hash(data: &ProjectData) -> Result<String> {
+    Ok(hex(&Sha256::digest(serde_json::to_vec(data)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Api, mock::MockServer, Config};
+
+    #[test]
+    fn export_new_and_changed_projects() {
+        let temp = tempfile::tempdir().unwrap();
+        let folder = temp.path();
+        let fixtures = folder.join("api");
+        fs::create_dir_all(&fixtures).unwrap();
+        let mock = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/mock");
+        for file in fs::read_dir(&mock).unwrap() {
+            let file = file.unwrap().path();
+            fs::copy(&file, fixtures.join(file.file_name().unwrap())).unwrap();
+        }
+
+        let server = MockServer::start(&fixtures, "127.0.0.1:0").unwrap();
+        let client = CoverClient::new(&Api {
+            url: Some(server.url()),
+            retries: Some(0),
+            ..Default::default()
+        })
+        .unwrap();
+        let exporter = Exporter::new(&Config::default()).unwrap();
+        let cache = ProjectCache::new(folder.join("cache"));
+        let watcher = Watcher::new(
+            &client,
+            &exporter,
+            &cache,
+            &Watch {
+                vendor: Some("csv".to_owned()),
+                status: Some("ready".to_owned()),
+                spool: Some(folder.join("spool")),
+                ..Default::default()
+            },
+            ExportOptions::default(),
+        )
+        .unwrap();
+
+        let uuids = |projects: Vec<Project>| {
+            projects
+                .into_iter()
+                .map(|project| project.uuid)
+                .collect::<Vec<_>>()
+        };
+        let downloads = || {
+            server
+                .requests()
+                .iter()
+                .filter(|request| request.contains("/documentData/"))
+                .count()
+        };
+        let first = "0488bf92-813f-4bbd-8e5f-16885d5b75df";
+        let second = "5a1e3c70-2b9d-4f8e-9c61-0d7f4b2e8a13";
+
+        assert_eq!(uuids(watcher.poll().unwrap()), [first]);
+        assert!(folder.join(format!("spool/{first}.csv")).is_file());
+        assert!(!folder
+            .join("spool/.partial")
+            .read_dir()
+            .unwrap()
+            .any(|_| true));
+
+        // Nothing changed : the data is not even downloaded
+        assert!(watcher.poll().unwrap().is_empty());
+        assert_eq!(downloads(), 1);
+
+        let projects = fs::read_to_string(fixtures.join("projects.json")).unwrap();
+        let projects = projects
+            .replace("2024-05-13T09:41:27Z", "2024-07-01T08:00:00Z")
+            .replace("DRAFT", "READY");
+        fs::write(fixtures.join("projects.json"), &projects).unwrap();
+
+        // New date with the same data, and a project now ready
+        assert_eq!(uuids(watcher.poll().unwrap()), [second]);
+        let state = WatchState::read(&folder.join("spool/.watch-state.json")).unwrap();
+        assert_eq!(
+            state.projects[first].date.as_deref(),
+            Some("2024-07-01T08:00:00Z")
+        );
+
+        let data = fs::read_to_string(fixtures.join(format!("{first}.json"))).unwrap();
+        fs::write(
+            fixtures.join(format!("{first}.json")),
+            data.replacen("1200.0", "1250.0", 1),
+        )
+        .unwrap();
+        fs::write(
+            fixtures.join("projects.json"),
+            projects.replace("2024-07-01T08:00:00Z", "2024-07-02T08:00:00Z"),
+        )
+        .unwrap();
+
+        assert_eq!(uuids(watcher.poll().unwrap()), [first]);
+        assert!(
+            fs::read_to_string(folder.join(format!("spool/{first}.csv")))
+                .unwrap()
+                .contains("1250")
+        );
+
+        // Projects leaving the status are forgotten
+        fs::write(
+            fixtures.join("projects.json"),
+            projects.replacen("READY", "DONE", 1),
+        )
+        .unwrap();
+        assert!(watcher.poll().unwrap().is_empty());
+        let state = WatchState::read(&folder.join("spool/.watch-state.json")).unwrap();
+        assert_eq!(state.projects.keys().collect::<Vec<_>>(), [second]);
+
+        // Without an update date, the data is checked at every poll
+        let projects = projects
+            .replacen("READY", "DONE", 1)
+            .replace("\"updatedAt\": \"2024-06-02", "\"createdAt\": \"2024-06-02");
+        fs::write(fixtures.join("projects.json"), projects).unwrap();
+        assert!(watcher.poll().unwrap().is_empty());
+
+        let data = fs::read_to_string(fixtures.join(format!("{second}.json"))).unwrap();
+        fs::write(
+            fixtures.join(format!("{second}.json")),
+            data.replacen("2400.0", "2450.0", 1),
+        )
+        .unwrap();
+        assert_eq!(uuids(watcher.poll().unwrap()), [second]);
+    }
+
+    #[test]
+    fn refuse_patterns_shared_by_projects() {
+        let client = CoverClient::new(&Api {
+            url: Some("http://localhost:5000".to_owned()),
+            ..Default::default()
+        })
+        .unwrap();
+        let exporter = Exporter::new(&Config::default()).unwrap();
+        let cache = ProjectCache::new("cache");
+        let watch = Watch {
+            vendor: Some("csv".to_owned()),
+            spool: Some(PathBuf::from("spool")),
+            ..Default::default()
+        };
+        let watcher = |pattern: &str| {
+            let options = ExportOptions {
+                name_pattern: Some(pattern.to_owned()),
+                ..Default::default()
+            };
+            Watcher::new(&client, &exporter, &cache, &watch, options).map(|_| ())
+        };
+
+        assert!(watcher("{project}_{view}").is_ok());
+        assert!(watcher("{view}").is_err());
+    }
+}
@@ -0,0 +1,211 @@
+//! Exports of `tests/fixtures/mock` projects through the mock Cover API, and
+//! uploads of the programs, the way `from-api` runs them.
+
+use std::{fs, path::Path};
+
+use lib::{
+    config::Api,
+    cover::DocumentMetadata,
+    mock::MockServer,
+    util::{find_projects, ProjectInfo},
+    ApiError, Config, CoverClient, ExportOptions, Exporter, Source,
+};
+
+fn start() -> (MockServer, CoverClient) {
+    let server = MockServer::start(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/mock"),
+        "127.0.0.1:0",
+    )
+    .unwrap();
+    let client = CoverClient::new(&Api {
+        url: Some(server.url()),
+        retries: Some(0),
+        ..Default::default()
+    })
+    .unwrap();
+
+    (server, client)
+}
+
+#[test]
+fn export_mock_projects() {
+    let (server, client) = start();
+
+    assert_eq!(client.projects().unwrap().len(), 2);
+    assert_eq!(
+        find_projects(&client, ProjectInfo::Name("IMPORT".to_owned()))
+            .unwrap()
+            .len(),
+        1
+    );
+    assert!(find_projects(&client, ProjectInfo::Id(1000))
+        .unwrap()
+        .is_empty());
+
+    let temp = tempfile::tempdir().unwrap();
+    let output = temp.path();
+    let exporter = Exporter::new(&Config::default()).unwrap();
+
+    for project in find_projects(&client, ProjectInfo::Name("port".to_owned())).unwrap() {
+        let data = client.project_data(&project.uuid).unwrap();
+        let file = output.join(format!("{}.csv", project.id.unwrap()));
+
+        exporter
+            .export(
+                Source::Api {
+                    project_uuid: project.uuid,
+                    data,
+                },
+                "csv",
+                Some(file.to_str().unwrap().to_owned()),
+                &ExportOptions::default(),
+            )
+            .unwrap();
+        assert!(!fs::read_to_string(&file).unwrap().is_empty());
+    }
+
+    assert!(server
+        .requests()
+        .contains(&"GET /project/search/findById?id=1000".to_owned()));
+}
+
+#[test]
+fn upload_exported_program() {
+    let (server, client) = start();
+    let project_uuid = "0488bf92-813f-4bbd-8e5f-16885d5b75df";
+
+    let temp = tempfile::tempdir().unwrap();
+    let output = temp.path();
+    let exporter = Exporter::new(&Config::default()).unwrap();
+
+    let source = Source::Api {
+        project_uuid: project_uuid.to_owned(),
+        data: client.project_data(project_uuid).unwrap(),
+    };
+    let exported = exporter
+        .export(
+            source,
+            "elumatec",
+            Some(output.to_str().unwrap().to_owned()),
+            &ExportOptions::default(),
+        )
+        .unwrap();
+    let files = exported.files;
+    assert_eq!(files, [output.join(format!("{project_uuid}.ncw"))]);
+    assert_eq!(exported.template.as_deref(), Some("elumatec"));
+
+    let metadata = DocumentMetadata {
+        project_uuid: project_uuid.to_owned(),
+        vendor: "elumatec".to_owned(),
+        exporter_version: "0.1.0".to_owned(),
+        template: exported.template,
+        template_hash: exported.template_hash,
+        timestamp: 1_760_000_000,
+    };
+    let document = client.upload(&files[0], &metadata).unwrap();
+    assert_eq!(document["id"], 1);
+
+    let uploads = server.uploads();
+    assert_eq!(uploads.len(), 1);
+    assert_eq!(uploads[0].project_uuid, project_uuid);
+    assert_eq!(uploads[0].file_name, format!("{project_uuid}.ncw"));
+    assert_eq!(uploads[0].contents, fs::read(&files[0]).unwrap());
+    assert_eq!(
+        serde_json::from_value::<DocumentMetadata>(uploads[0].metadata.clone()).unwrap(),
+        metadata
+    );
+    assert_eq!(
+        uploads[0].metadata["templateHash"].as_str().unwrap().len(),
+        64
+    );
+
+    // Documents of unknown projects are refused
+    let err = client
+        .upload(
+            &files[0],
+            &DocumentMetadata {
+                project_uuid: "unknown".to_owned(),
+                ..metadata
+            },
+        )
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ApiError>()
+            .unwrap()
+            .status
+            .map(|status| status.as_u16()),
+        Some(404)
+    );
+    assert_eq!(server.uploads().len(), 1);
+}
+
+#[test]
+fn send_upload_once() {
+    let (server, _) = start();
+    let client = CoverClient::new(&Api {
+        url: Some(server.url()),
+        retries: Some(3),
+        ..Default::default()
+    })
+    .unwrap();
+
+    let temp = tempfile::tempdir().unwrap();
+    let file = temp.path().join("program.ncw");
+    fs::write(&file, "program").unwrap();
+
+    // The server fails after storing the document : retrying would duplicate it
+    server.fail_uploads(1);
+    let err = client
+        .upload(
+            &file,
+            &DocumentMetadata {
+                project_uuid: "0488bf92-813f-4bbd-8e5f-16885d5b75df".to_owned(),
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ApiError>()
+            .unwrap()
+            .status
+            .map(|status| status.as_u16()),
+        Some(503)
+    );
+    assert_eq!(server.uploads().len(), 1);
+}
+
+#[test]
+fn refuse_empty_uploads() {
+    let (server, client) = start();
+    let project_uuid = "5a1e3c70-2b9d-4f8e-9c61-0d7f4b2e8a13";
+
+    let temp = tempfile::tempdir().unwrap();
+    let output = temp.path();
+    let exporter = Exporter::new(&Config::default()).unwrap();
+
+    // G-code programs are only written for parts with machinings
+    let exported = exporter
+        .export(
+            Source::Api {
+                project_uuid: project_uuid.to_owned(),
+                data: client.project_data(project_uuid).unwrap(),
+            },
+            "gcode",
+            Some(output.to_str().unwrap().to_owned()),
+            &ExportOptions::default(),
+        )
+        .unwrap();
+    assert!(exported.files.is_empty());
+
+    let err = client
+        .upload_files(
+            &exported.files,
+            &DocumentMetadata {
+                project_uuid: project_uuid.to_owned(),
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+    assert!(err.to_string().starts_with("No file to upload"));
+    assert!(server.uploads().is_empty());
+}
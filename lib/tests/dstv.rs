@@ -0,0 +1,64 @@
+//! Golden files of the DSTV exporter : each `golden/dstv/<name>.json` source
+//! must produce exactly the files of `golden/dstv/<name>/`.
+//! Run with `UPDATE_GOLDEN=1` to regenerate them after an intended change.
+
+use std::{fs, path::Path};
+
+use lib::{util::find_files_with_extension, Config, ExportOptions, Exporter, Source};
+
+#[test]
+fn dstv_golden_files() {
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/dstv");
+    let sources = find_files_with_extension(golden.to_str().unwrap(), "json").unwrap();
+    assert!(!sources.is_empty());
+
+    let exporter = Exporter::new(&Config::default()).unwrap();
+
+    for source in sources {
+        let name = Path::new(&source).file_stem().unwrap().to_str().unwrap();
+        let temp = tempfile::tempdir().unwrap();
+        let output = temp.path();
+
+        exporter
+            .export(
+                Source::File(source.clone()),
+                "dstv",
+                Some(output.to_str().unwrap().to_owned()),
+                &ExportOptions::default(),
+            )
+            .unwrap();
+
+        let expected = golden.join(name);
+        if std::env::var("UPDATE_GOLDEN").is_ok() {
+            let _ = fs::remove_dir_all(&expected);
+            fs::create_dir_all(&expected).unwrap();
+            for entry in fs::read_dir(output).unwrap() {
+                let path = entry.unwrap().path();
+                fs::copy(&path, expected.join(path.file_name().unwrap())).unwrap();
+            }
+        }
+
+        let mut generated = fs::read_dir(output)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        generated.sort();
+
+        let mut files = fs::read_dir(&expected)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        files.sort();
+
+        assert_eq!(generated, files, "unexpected files generated for {name}");
+
+        for file in files {
+            assert_eq!(
+                fs::read_to_string(output.join(&file)).unwrap(),
+                fs::read_to_string(expected.join(&file)).unwrap(),
+                "{name}/{} differs from its golden file",
+                file.to_string_lossy()
+            );
+        }
+    }
+}